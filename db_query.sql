@@ -127,4 +127,171 @@ SELECT l1.id as category_id, l1.category_name as category_name, array_agg(l2.id)
 FROM db.categories l1
 LEFT JOIN db.categories l2 ON l1.id = l2.parent_id
 WHERE l1.parent_id IS NULL
-GROUP BY l1.id, l1.category_name;
\ No newline at end of file
+GROUP BY l1.id, l1.category_name;
+
+CREATE TABLE "db"."course_attendance" (
+  "booking_id" uuid,
+  "session" int,
+  "status" varchar CHECK ("status" IN ('present', 'absent', 'excused')),
+  "marked_at" timestamptz,
+  PRIMARY KEY ("booking_id", "session")
+);
+
+ALTER TABLE "db"."course_attendance" ADD FOREIGN KEY ("booking_id") REFERENCES "db"."course_bookings" ("id");
+
+CREATE VIEW db.course_attendance_info AS
+SELECT cb.id as booking_id, cb.course_id, u.first_name, u.last_name, array_agg(ca.session) as sessions, array_agg(ca.status) as statuses
+FROM db.course_bookings cb
+INNER JOIN db.user u ON cb.user_id = u.id
+LEFT JOIN db.course_attendance ca ON cb.id = ca.booking_id
+GROUP BY cb.id, cb.course_id, u.first_name, u.last_name;
+
+CREATE TABLE "db"."certificates" (
+  "id" uuid PRIMARY KEY,
+  "booking_id" uuid UNIQUE,
+  "code" varchar UNIQUE,
+  "issuer" varchar,
+  "issued_at" timestamptz
+);
+
+ALTER TABLE "db"."certificates" ADD FOREIGN KEY ("booking_id") REFERENCES "db"."course_bookings" ("id");
+
+CREATE VIEW db.certificate_info AS
+SELECT cert.code, cert.booking_id, cb.course_id, u.first_name, u.last_name, c.course_name, c.start_date, c.end_date, c.sessions, cert.issuer, cert.issued_at
+FROM db.certificates cert
+INNER JOIN db.course_bookings cb ON cert.booking_id = cb.id
+INNER JOIN db.user u ON cb.user_id = u.id
+INNER JOIN db.courses c ON cb.course_id = c.id;
+
+ALTER TABLE "db"."categories" ADD COLUMN "sort_order" int NOT NULL DEFAULT 0;
+
+ALTER TABLE "db"."categories" ADD CHECK ("parent_id" <> "id");
+
+DROP VIEW db.category_subcategories;
+
+CREATE VIEW db.category_tree AS
+WITH RECURSIVE tree AS (
+  SELECT id, category_name, parent_id, sort_order, 0 as depth, ARRAY[id] as path
+  FROM db.categories
+  WHERE parent_id IS NULL
+  UNION ALL
+  SELECT c.id, c.category_name, c.parent_id, c.sort_order, t.depth + 1, t.path || c.id
+  FROM db.categories c
+  INNER JOIN tree t ON c.parent_id = t.id
+)
+SELECT * FROM tree;
+
+CREATE VIEW db.category_descendants AS
+WITH RECURSIVE descendants AS (
+  SELECT id as ancestor_id, id as descendant_id
+  FROM db.categories
+  UNION
+  SELECT d.ancestor_id, c.id
+  FROM db.categories c
+  INNER JOIN descendants d ON c.parent_id = d.descendant_id
+)
+SELECT * FROM descendants;
+
+CREATE COLLATION "db"."swedish" (provider = icu, locale = 'sv-SE');
+
+ALTER TABLE "db"."locations" ADD COLUMN "sort_order" int NOT NULL DEFAULT 0;
+
+ALTER TABLE "db"."locations" ADD COLUMN "slug" varchar;
+
+ALTER TABLE "db"."categories" ADD COLUMN "slug" varchar;
+
+-- The slugs helpers::slugify makes in the API, kept the same by a test
+CREATE FUNCTION "db"."slugify"(name text) RETURNS text AS $$
+  SELECT trim(both '-' from regexp_replace(lower(translate(name,
+    'åäàáÅÄÀÁöøóÖØÓéèêëÉÈÊËüúÜÚ', 'aaaaaaaaooooooeeeeeeeeuuuu')), '[^a-z0-9]+', '-', 'g'))
+$$ LANGUAGE sql IMMUTABLE;
+
+-- Backfill slugs for existing rows, duplicates get part of their id appended
+UPDATE db.locations SET slug = db.slugify(name);
+
+UPDATE db.locations l SET slug = l.slug || '-' || left(l.id::text, 8)
+WHERE l.slug = '' OR EXISTS (SELECT 1 FROM db.locations o WHERE o.slug = l.slug AND o.id < l.id);
+
+UPDATE db.categories SET slug = db.slugify(category_name);
+
+UPDATE db.categories c SET slug = c.slug || '-' || left(c.id::text, 8)
+WHERE c.slug = '' OR EXISTS (SELECT 1 FROM db.categories o WHERE o.slug = c.slug AND o.id < c.id);
+
+ALTER TABLE "db"."locations" ALTER COLUMN "slug" SET NOT NULL;
+
+ALTER TABLE "db"."locations" ADD UNIQUE ("slug");
+
+ALTER TABLE "db"."categories" ALTER COLUMN "slug" SET NOT NULL;
+
+ALTER TABLE "db"."categories" ADD UNIQUE ("slug");
+
+DROP VIEW db.district_cities;
+
+CREATE VIEW db.district_cities AS
+SELECT l1.id as district_id, l1.name as district_name, l1.slug as district_slug, l1.sort_order,
+  array_agg(l2.id ORDER BY l2.sort_order, l2.name COLLATE "db"."swedish") as cities_id,
+  array_agg(l2.name ORDER BY l2.sort_order, l2.name COLLATE "db"."swedish") as cities_name,
+  array_agg(l2.slug ORDER BY l2.sort_order, l2.name COLLATE "db"."swedish") as cities_slug
+FROM db.locations l1
+LEFT JOIN db.locations l2 ON l1.id = l2.parent_id
+WHERE l1.parent_id IS NULL
+GROUP BY l1.id, l1.name, l1.slug, l1.sort_order;
+
+DROP VIEW db.category_tree;
+
+CREATE VIEW db.category_tree AS
+WITH RECURSIVE tree AS (
+  SELECT id, category_name, slug, parent_id, sort_order, 0 as depth, ARRAY[id] as path
+  FROM db.categories
+  WHERE parent_id IS NULL
+  UNION ALL
+  SELECT c.id, c.category_name, c.slug, c.parent_id, c.sort_order, t.depth + 1, t.path || c.id
+  FROM db.categories c
+  INNER JOIN tree t ON c.parent_id = t.id
+)
+SELECT * FROM tree;
+
+-- Location names only have to be unique among locations with the same parent
+ALTER TABLE "db"."locations" DROP CONSTRAINT "locations_name_key";
+
+CREATE UNIQUE INDEX "locations_district_name_key" ON "db"."locations" ("name") WHERE "parent_id" IS NULL;
+
+CREATE UNIQUE INDEX "locations_city_name_key" ON "db"."locations" ("parent_id", "name") WHERE "parent_id" IS NOT NULL;
+
+-- Bookings point at the kommun they were made from
+ALTER TABLE "db"."user" ADD COLUMN "kommun_id" uuid;
+
+ALTER TABLE "db"."user" ADD FOREIGN KEY ("kommun_id") REFERENCES "db"."locations" ("id");
+
+UPDATE "db"."user" u SET "kommun_id" = l.id
+FROM "db"."locations" l
+WHERE l.parent_id IS NOT NULL AND lower(trim(u.kommun)) = lower(l.name);
+
+-- Postnummer are stored as their five digits, without the space
+ALTER TABLE "db"."user" ALTER COLUMN "zipcode" TYPE varchar(5) USING lpad("zipcode"::text, 5, '0');
+
+-- Responses to POSTs sent with an Idempotency-Key, replayed when the request is
+-- retried. Rows without a status belong to requests still being handled.
+CREATE TABLE "db"."idempotency_keys" (
+  "operation" varchar,
+  "key" varchar,
+  "request_hash" varchar NOT NULL,
+  "status" int,
+  "body" text,
+  "created_at" timestamptz NOT NULL,
+  PRIMARY KEY ("operation", "key")
+);
+
+CREATE INDEX ON "db"."idempotency_keys" ("created_at");
+
+-- Replayed creations point to what was created again
+ALTER TABLE "db"."idempotency_keys" ADD COLUMN "location" varchar;
+
+-- The version of this schema, which /readyz compares with SCHEMA_VERSION in
+-- src/queries.rs. Every later change to the schema ends by raising both, e.g.
+-- UPDATE "db"."schema_version" SET "version" = 2;
+CREATE TABLE "db"."schema_version" (
+  "version" int NOT NULL
+);
+
+INSERT INTO "db"."schema_version" ("version") VALUES (1);
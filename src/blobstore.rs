@@ -0,0 +1,520 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use actix_web::web::{self, Bytes};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{header::CONTENT_TYPE, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+// A stored file and what it holds
+#[derive(Clone)]
+pub struct Blob {
+    pub bytes: Bytes,
+    pub content_type: String,
+}
+
+// Somewhere to keep uploaded files by key, like "courses/{id}/{hash}/original.png".
+// Keys are relative paths of lowercase letters, digits, '-', '.' and '/'.
+// Chosen with BLOB_STORE when running.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    // Stores the bytes under the key, replacing whatever was there
+    async fn put(&self, key: &str, bytes: Bytes, content_type: &str) -> io::Result<()>;
+
+    // The blob under the key, None if nothing was stored there
+    async fn get(&self, key: &str) -> io::Result<Option<Blob>>;
+}
+
+// Whether `key` can be used as a key: no empty, `.` or `..` segments and
+// nothing that would need escaping in a path or URL
+pub fn valid_key(key: &str) -> bool {
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "-./".contains(c);
+
+    return !key.is_empty()
+        && key.chars().all(allowed)
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+}
+
+fn invalid_key(key: &str) -> io::Error {
+    return io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("\"{}\" is not a valid blob key", key),
+    );
+}
+
+// Files below a directory on the local disk, the default
+pub struct LocalStore {
+    pub root: PathBuf,
+}
+
+impl LocalStore {
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if !valid_key(key) {
+            return Err(invalid_key(key));
+        }
+
+        return Ok(self.root.join(key));
+    }
+}
+
+// The content type is not kept on disk, it follows from the extension
+fn content_type_of(path: &Path) -> &'static str {
+    return match path.extension().and_then(|extension| extension.to_str()) {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    };
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, bytes: Bytes, _content_type: &str) -> io::Result<()> {
+        let path = self.path(key)?;
+
+        // Written next to the file and renamed, so readers never see half of it
+        return web::block(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let partial = path.with_extension("partial");
+            std::fs::write(&partial, &bytes)?;
+            return std::fs::rename(&partial, &path);
+        })
+        .await
+        .map_err(io::Error::other)?;
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Blob>> {
+        let path = self.path(key)?;
+
+        return web::block(move || match std::fs::read(&path) {
+            Ok(bytes) => Ok(Some(Blob {
+                bytes: Bytes::from(bytes),
+                content_type: content_type_of(&path).to_string(),
+            })),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        })
+        .await
+        .map_err(io::Error::other)?;
+    }
+}
+
+// Blobs kept in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    blobs: std::sync::Mutex<std::collections::HashMap<String, Blob>>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl BlobStore for MemoryStore {
+    async fn put(&self, key: &str, bytes: Bytes, content_type: &str) -> io::Result<()> {
+        if !valid_key(key) {
+            return Err(invalid_key(key));
+        }
+
+        let blob = Blob {
+            bytes,
+            content_type: content_type.to_string(),
+        };
+        self.blobs.lock().unwrap().insert(key.to_string(), blob);
+
+        return Ok(());
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Blob>> {
+        return Ok(self.blobs.lock().unwrap().get(key).cloned());
+    }
+}
+
+// A bucket in S3 or anything speaking its API, like MinIO. Objects are
+// addressed by path (`{endpoint}/{bucket}/{key}`) and requests signed with
+// AWS Signature Version 4.
+pub struct S3Store {
+    pub endpoint: Url,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub client: reqwest::Client,
+}
+
+impl S3Store {
+    fn url(&self, key: &str) -> io::Result<Url> {
+        if !valid_key(key) {
+            return Err(invalid_key(key));
+        }
+
+        let path = format!(
+            "{}/{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            self.bucket,
+            key
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        return Ok(url);
+    }
+
+    // The request with the headers S3 wants signed and the signature itself
+    fn signed(
+        &self,
+        request: reqwest::RequestBuilder,
+        method: &str,
+        url: &Url,
+        payload: &[u8],
+    ) -> reqwest::RequestBuilder {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = format!("{:x}", Sha256::digest(payload));
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let authorization = authorization(&Signing {
+            method,
+            path: url.path(),
+            host: &host,
+            payload_hash: &payload_hash,
+            amz_date: &amz_date,
+            date: &date,
+            region: &self.region,
+            access_key_id: &self.access_key_id,
+            secret_access_key: &self.secret_access_key,
+        });
+
+        return request
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization);
+    }
+}
+
+fn s3_error(action: &str, key: &str, status: StatusCode) -> io::Error {
+    return io::Error::other(format!(
+        "S3 answered {} when {} \"{}\"",
+        status, action, key
+    ));
+}
+
+#[async_trait]
+impl BlobStore for S3Store {
+    async fn put(&self, key: &str, bytes: Bytes, content_type: &str) -> io::Result<()> {
+        let url = self.url(key)?;
+        let request = self
+            .client
+            .put(url.clone())
+            .header(CONTENT_TYPE, content_type);
+        let response = self
+            .signed(request, "PUT", &url, &bytes)
+            .body(bytes)
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+
+        if !response.status().is_success() {
+            return Err(s3_error("storing", key, response.status()));
+        }
+
+        return Ok(());
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Blob>> {
+        let url = self.url(key)?;
+        let request = self.client.get(url.clone());
+        let response = self
+            .signed(request, "GET", &url, b"")
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => return Err(s3_error("fetching", key, status)),
+            _ => {}
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let bytes = response.bytes().await.map_err(io::Error::other)?;
+
+        return Ok(Some(Blob {
+            bytes,
+            content_type,
+        }));
+    }
+}
+
+// What goes into the signature of a request without a query string
+struct Signing<'a> {
+    method: &'a str,
+    path: &'a str,
+    host: &'a str,
+    payload_hash: &'a str,
+    amz_date: &'a str,
+    date: &'a str,
+    region: &'a str,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+}
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+// The Authorization header for the request, as described in
+// https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html
+fn authorization(signing: &Signing) -> String {
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        signing.method,
+        signing.path,
+        signing.host,
+        signing.payload_hash,
+        signing.amz_date,
+        SIGNED_HEADERS,
+        signing.payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", signing.date, signing.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        signing.amz_date,
+        scope,
+        Sha256::digest(canonical_request)
+    );
+
+    let key = signing_key(
+        signing.secret_access_key,
+        signing.date,
+        signing.region,
+        "s3",
+    );
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    return format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        signing.access_key_id, scope, SIGNED_HEADERS, signature
+    );
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(
+        format!("AWS4{}", secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    return hmac(&key, b"aws4_request");
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    return mac.finalize().into_bytes().to_vec();
+}
+
+fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+// BLOB_STORE=s3 stores in the bucket S3_BUCKET at S3_ENDPOINT (default
+// https://s3.{S3_REGION}.amazonaws.com) in S3_REGION (default eu-north-1),
+// with the keys S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY. Anything else
+// stores below BLOB_DIR (default "uploads").
+pub fn from_env() -> Box<dyn BlobStore> {
+    let var = |name: &str| std::env::var(name).ok();
+
+    match var("BLOB_STORE").as_deref() {
+        Some("s3") => {
+            let region = var("S3_REGION").unwrap_or_else(|| String::from("eu-north-1"));
+            let endpoint = var("S3_ENDPOINT")
+                .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+
+            return Box::new(S3Store {
+                endpoint: Url::parse(&endpoint).expect("Invalid S3_ENDPOINT"),
+                bucket: var("S3_BUCKET").expect("S3_BUCKET must be set"),
+                region,
+                access_key_id: var("S3_ACCESS_KEY_ID").expect("S3_ACCESS_KEY_ID must be set"),
+                secret_access_key: var("S3_SECRET_ACCESS_KEY")
+                    .expect("S3_SECRET_ACCESS_KEY must be set"),
+                client: reqwest::Client::new(),
+            });
+        }
+        _ => {
+            let root = var("BLOB_DIR").unwrap_or_else(|| String::from("uploads"));
+            return Box::new(LocalStore { root: root.into() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use actix_web::{
+        http::Method,
+        web::{self, Bytes, Data},
+        App, HttpRequest, HttpResponse, HttpServer,
+    };
+    use reqwest::Url;
+    use sha2::{Digest, Sha256};
+    use uuid::Uuid;
+
+    use super::{
+        authorization, hex, signing_key, valid_key, BlobStore, LocalStore, S3Store, Signing,
+    };
+
+    #[test]
+    fn derives_the_signing_key_like_aws() {
+        // The example from the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn only_takes_keys_below_the_root() {
+        assert!(valid_key("courses/1f0e/3a9c/original.png"));
+        assert!(!valid_key(""));
+        assert!(!valid_key("../etc/passwd"));
+        assert!(!valid_key("courses/./original.png"));
+        assert!(!valid_key("/courses/original.png"));
+        assert!(!valid_key("courses//original.png"));
+        assert!(!valid_key("courses/Original.png"));
+        assert!(!valid_key("courses/original png"));
+    }
+
+    #[actix_web::test]
+    async fn keeps_blobs_on_disk() {
+        let root = std::env::temp_dir().join(format!("ibnrushd-api-{}", Uuid::new_v4()));
+        let store = LocalStore { root: root.clone() };
+
+        let bytes = Bytes::from_static(b"\x89PNG...");
+        store
+            .put("courses/1/original.png", bytes.clone(), "image/png")
+            .await
+            .unwrap();
+
+        let blob = store.get("courses/1/original.png").await.unwrap().unwrap();
+        assert_eq!(blob.bytes, bytes);
+        assert_eq!(blob.content_type, "image/png");
+        assert!(store.get("courses/2/original.png").await.unwrap().is_none());
+        assert!(store.get("../original.png").await.is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    // Stands in for S3: keeps objects by path and refuses requests whose
+    // signature does not match the one made with its own copy of the secret
+    #[derive(Default)]
+    struct Bucket {
+        objects: Mutex<HashMap<String, (Bytes, String)>>,
+    }
+
+    async fn bucket(request: HttpRequest, body: Bytes, bucket: Data<Bucket>) -> HttpResponse {
+        let header = |name: &str| {
+            return request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+        };
+
+        let amz_date = header("x-amz-date");
+        let payload_hash = header("x-amz-content-sha256");
+        let expected = authorization(&Signing {
+            method: request.method().as_str(),
+            path: request.path(),
+            host: &header("host"),
+            payload_hash: &payload_hash,
+            amz_date: &amz_date,
+            date: amz_date.get(..8).unwrap_or_default(),
+            region: "eu-north-1",
+            access_key_id: "minio",
+            secret_access_key: "minio-secret",
+        });
+        if payload_hash != format!("{:x}", Sha256::digest(&body))
+            || header("authorization") != expected
+        {
+            return HttpResponse::Forbidden().finish();
+        }
+
+        let mut objects = bucket.objects.lock().unwrap();
+        return match *request.method() {
+            Method::PUT => {
+                objects.insert(request.path().to_string(), (body, header("content-type")));
+                HttpResponse::Ok().finish()
+            }
+            _ => match objects.get(request.path()) {
+                Some((bytes, content_type)) => HttpResponse::Ok()
+                    .content_type(content_type.as_str())
+                    .body(bytes.clone()),
+                None => HttpResponse::NotFound().finish(),
+            },
+        };
+    }
+
+    #[actix_web::test]
+    async fn keeps_blobs_in_an_s3_bucket() {
+        let state = Data::new(Bucket::default());
+        let server = HttpServer::new(move || {
+            return App::new()
+                .app_data(state.clone())
+                .default_service(web::to(bucket));
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        let store = |secret_access_key: &str| S3Store {
+            endpoint: Url::parse(&format!("http://{}", address)).unwrap(),
+            bucket: String::from("images"),
+            region: String::from("eu-north-1"),
+            access_key_id: String::from("minio"),
+            secret_access_key: secret_access_key.to_string(),
+            client: reqwest::Client::new(),
+        };
+        let store_with_wrong_secret = store("wrong");
+        let store = store("minio-secret");
+
+        let bytes = Bytes::from_static(b"\xff\xd8\xff...");
+        store
+            .put("courses/1/large.jpg", bytes.clone(), "image/jpeg")
+            .await
+            .unwrap();
+
+        let blob = store.get("courses/1/large.jpg").await.unwrap().unwrap();
+        assert_eq!(blob.bytes, bytes);
+        assert_eq!(blob.content_type, "image/jpeg");
+        assert!(store.get("courses/2/large.jpg").await.unwrap().is_none());
+
+        assert!(store_with_wrong_secret
+            .put("courses/1/large.jpg", bytes, "image/jpeg")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::models::api::CreateBookingRequest;
+
+// The header clients put their proof of being a person in
+pub const PROOF_HEADER: &str = "x-booking-proof";
+
+// Anything that can tell whether a booking was made by a person rather than a
+// script, from the proof the client sent along: a token for a captcha service
+// to verify, or a nonce for proof of work. Chosen with BOT_CHECK when running.
+#[async_trait]
+pub trait BotCheck: Send + Sync {
+    async fn verify(&self, proof: Option<&str>, booking: &CreateBookingRequest) -> bool;
+}
+
+// Lets every booking through, the default
+pub struct NoBotCheck;
+
+#[async_trait]
+impl BotCheck for NoBotCheck {
+    async fn verify(&self, _proof: Option<&str>, _booking: &CreateBookingRequest) -> bool {
+        return true;
+    }
+}
+
+// The proof is a nonce for which the SHA-256 of "{course_id}:{personal_number}:{nonce}"
+// starts with `difficulty` zero bits. Costs the client about 2^difficulty hashes
+// per booking and the server one.
+pub struct ProofOfWork {
+    pub difficulty: u32,
+}
+
+#[async_trait]
+impl BotCheck for ProofOfWork {
+    async fn verify(&self, proof: Option<&str>, booking: &CreateBookingRequest) -> bool {
+        let nonce = match proof {
+            Some(nonce) => nonce,
+            None => return false,
+        };
+
+        let hash = Sha256::digest(format!(
+            "{}:{}:{}",
+            booking.course_id, booking.personal_number, nonce
+        ));
+
+        return leading_zero_bits(&hash) >= self.difficulty;
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut zeros = 0;
+
+    for byte in bytes {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    return zeros;
+}
+
+// BOT_CHECK=proof-of-work turns on proof of work, with BOT_CHECK_DIFFICULTY
+// leading zero bits (default 16). Anything else lets every booking through.
+pub fn from_env() -> Box<dyn BotCheck> {
+    match std::env::var("BOT_CHECK").as_deref() {
+        Ok("proof-of-work") => {
+            let difficulty = std::env::var("BOT_CHECK_DIFFICULTY")
+                .ok()
+                .and_then(|difficulty| difficulty.parse().ok())
+                .unwrap_or(16);
+            return Box::new(ProofOfWork { difficulty });
+        }
+        _ => return Box::new(NoBotCheck),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::leading_zero_bits;
+
+    #[test]
+    fn counts_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0, 0, 0x0f, 0xff]), 20);
+        assert_eq!(leading_zero_bits(&[0x80, 0]), 0);
+        assert_eq!(leading_zero_bits(&[0, 0]), 16);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{self, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch},
+        Method, StatusCode,
+    },
+    web::{Bytes, Data},
+    Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{telemetry::internal_error, AppState};
+
+// Responses kept before the cache starts over
+const MAX_ENTRIES: usize = 1_000;
+
+// Lets browsers and CDNs keep responses as long as they check the ETag first
+const CACHE_CONTROL: &str = "public, no-cache";
+
+// The catalogue responses (courses, categories and locations) as last sent,
+// by path and query. Emptied by every successful write to the catalogue and
+// otherwise kept for `ttl`, so changes made outside this process, like
+// `ibnrushd-api seed-locations`, show up too. Read from the environment when
+// running:
+//   CATALOGUE_CACHE_SECONDS  how long responses are kept (default 60)
+pub struct CatalogueCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    // Counts invalidations, so responses read before a write are not kept after it
+    generation: u64,
+    responses: HashMap<String, CachedResponse>,
+}
+
+#[derive(Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    body: Bytes,
+    etag: EntityTag,
+    last_modified: SystemTime,
+    stored: Instant,
+}
+
+impl CatalogueCache {
+    pub fn new(ttl: Duration) -> CatalogueCache {
+        return CatalogueCache {
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        };
+    }
+
+    pub fn from_env() -> CatalogueCache {
+        let ttl = std::env::var("CATALOGUE_CACHE_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .map_or(Duration::from_secs(60), Duration::from_secs);
+
+        return CatalogueCache::new(ttl);
+    }
+
+    // Forgets every response, after the catalogue changed
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.responses.clear();
+    }
+
+    // The current generation and the response for `key`, if it is still fresh
+    fn get(&self, key: &str) -> (u64, Option<CachedResponse>) {
+        let entries = self.entries.lock().unwrap();
+        let cached = entries
+            .responses
+            .get(key)
+            .filter(|cached| cached.stored.elapsed() < self.ttl)
+            .cloned();
+
+        return (entries.generation, cached);
+    }
+
+    // Keeps `body` for `key`, unless the catalogue changed since `generation`
+    // was read. A body equal to the one it replaces keeps its Last-Modified.
+    fn store(&self, key: &str, generation: u64, status: StatusCode, body: Bytes) -> CachedResponse {
+        let etag = EntityTag::new_strong(format!("{:x}", Sha256::digest(&body)));
+        let mut entries = self.entries.lock().unwrap();
+
+        let last_modified = match entries.responses.get(key) {
+            Some(previous) if previous.etag.strong_eq(&etag) => previous.last_modified,
+            _ => whole_seconds(SystemTime::now()),
+        };
+        let cached = CachedResponse {
+            status,
+            body,
+            etag,
+            last_modified,
+            stored: Instant::now(),
+        };
+
+        if entries.generation == generation {
+            if entries.responses.len() >= MAX_ENTRIES {
+                entries.responses.clear();
+            }
+            entries.responses.insert(key.to_string(), cached.clone());
+        }
+
+        return cached;
+    }
+}
+
+// HTTP dates have no fractions of a second, so Last-Modified is compared without them
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    return UNIX_EPOCH + Duration::from_secs(seconds);
+}
+
+// Wraps a catalogue resource: GETs are answered from AppState::catalogue with
+// an ETag and Last-Modified, or a 304 when the client has them already. Any
+// other request that succeeds empties the cache.
+pub struct Cached;
+
+impl<S, B> Transform<S, ServiceRequest> for Cached
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = CachedMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        return ready(Ok(CachedMiddleware {
+            service: Rc::new(service),
+        }));
+    }
+}
+
+pub struct CachedMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CachedMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        return Box::pin(handle(req, Rc::clone(&self.service)));
+    }
+}
+
+async fn handle<S, B>(
+    req: ServiceRequest,
+    service: Rc<S>,
+) -> Result<ServiceResponse<BoxBody>, Error>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody + 'static,
+{
+    let state = match req.app_data::<Data<AppState>>().cloned() {
+        Some(state) => state,
+        None => return Ok(service.call(req).await?.map_into_boxed_body()),
+    };
+
+    if req.method() != Method::GET {
+        let response = service.call(req).await?;
+        if response.status().is_success() {
+            state.catalogue.invalidate();
+        }
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let key = req.uri().to_string();
+    let (generation, cached) = state.catalogue.get(&key);
+
+    if let Some(cached) = cached {
+        let (request, _) = req.into_parts();
+        let response = respond(&request, &cached);
+        return Ok(ServiceResponse::new(request, response));
+    }
+
+    let response = service.call(req).await?;
+    if !response.status().is_success() {
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let (request, response) = response.into_parts();
+    let status = response.status();
+    let body = match body::to_bytes(response.into_body()).await {
+        Ok(body) => body,
+        Err(err) => {
+            let err: Box<dyn StdError> = err.into();
+            let response = internal_error("Error reading the response", &*err);
+            return Ok(ServiceResponse::new(request, response));
+        }
+    };
+
+    let cached = state.catalogue.store(&key, generation, status, body);
+    let response = respond(&request, &cached);
+
+    return Ok(ServiceResponse::new(request, response));
+}
+
+// Every catalogue response is JSON
+fn respond(request: &HttpRequest, cached: &CachedResponse) -> HttpResponse {
+    let not_modified = not_modified(request, cached);
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::build(cached.status)
+    };
+    response
+        .insert_header(header::ETag(cached.etag.clone()))
+        .insert_header(header::LastModified(cached.last_modified.into()))
+        .insert_header((header::CACHE_CONTROL, CACHE_CONTROL));
+
+    if not_modified {
+        return response.finish();
+    }
+    return response
+        .content_type("application/json")
+        .body(cached.body.clone());
+}
+
+// If-None-Match decides when it is sent, If-Modified-Since otherwise (RFC 9110 13.2.2)
+fn not_modified(request: &HttpRequest, cached: &CachedResponse) -> bool {
+    if let Some(if_none_match) = request.get_header::<IfNoneMatch>() {
+        return match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(etags) => etags.iter().any(|etag| etag.weak_eq(&cached.etag)),
+        };
+    }
+
+    if let Some(IfModifiedSince(since)) = request.get_header::<IfModifiedSince>() {
+        return HttpDate::from(cached.last_modified) <= since;
+    }
+
+    return false;
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use serde_json::json;
+
+    use crate::{
+        tests::{fixtures, post, send, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn answers_not_modified_to_clients_with_the_catalogue() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        fixtures(&app).await;
+
+        let response = test::call_service(
+            &app,
+            TestRequest::get().uri("/api/v1/catalogue").to_request(),
+        )
+        .await;
+        assert!(response.status().is_success());
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        let last_modified = response
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .unwrap()
+            .clone();
+
+        let request = TestRequest::get()
+            .uri("/api/v1/catalogue")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), etag);
+        assert!(test::read_body(response).await.is_empty());
+
+        let request = TestRequest::get()
+            .uri("/api/v1/catalogue")
+            .insert_header((header::IF_MODIFIED_SINCE, last_modified));
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        // If-None-Match wins over If-Modified-Since
+        let request = TestRequest::get()
+            .uri("/api/v1/catalogue")
+            .insert_header((header::IF_NONE_MATCH, "\"something-else\""))
+            .insert_header((header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT"));
+        let (status, catalogue) = send(&app, request).await;
+        assert!(status.is_success());
+        assert_eq!(
+            catalogue["categories"][0]["category_name"],
+            "Språk & kultur"
+        );
+    }
+
+    #[actix_web::test]
+    async fn forgets_the_catalogue_when_it_changes() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        fixtures(&app).await;
+
+        let response = test::call_service(
+            &app,
+            TestRequest::get().uri("/api/v1/categories").to_request(),
+        )
+        .await;
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+        let (status, _) = post(
+            &app,
+            "/api/v1/categories",
+            json!({ "category_name": "Religion" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let request = TestRequest::get()
+            .uri("/api/v1/categories")
+            .insert_header((header::IF_NONE_MATCH, etag));
+        let (status, tree) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tree.as_array().unwrap().len(), 2);
+
+        assert!(state.catalogue.get("/api/v1/categories").1.is_some());
+        let (status, _) = post(&app, "/api/v1/categories", json!({ "category_name": "" })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(state.catalogue.get("/api/v1/categories").1.is_some());
+    }
+}
@@ -0,0 +1,146 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::models::db::CertificateInfo;
+
+// A4 landscape, measured in points
+const PAGE_WIDTH: f32 = 842.0;
+const PAGE_HEIGHT: f32 = 595.0;
+const MARGIN: f32 = 72.0;
+
+const REGULAR_FONT: Name = Name(b"F1");
+const BOLD_FONT: Name = Name(b"F2");
+
+// The built-in PDF fonts only understand single byte encodings, so text is
+// converted to WinAnsi which covers the Swedish characters å, ä and ö.
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    return text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect();
+}
+
+fn write_line(content: &mut Content, font: Name, size: f32, y: f32, text: &str) {
+    content.begin_text();
+    content.set_font(font, size);
+    content.next_line(MARGIN, y);
+    content.show(Str(&encode_win_ansi(text)));
+    content.end_text();
+}
+
+pub fn render_certificate(certificate: &CertificateInfo) -> Vec<u8> {
+    let mut pdf = Pdf::new();
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let regular_font_id = Ref::new(4);
+    let bold_font_id = Ref::new(5);
+    let content_id = Ref::new(6);
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    page.resources()
+        .fonts()
+        .pair(REGULAR_FONT, regular_font_id)
+        .pair(BOLD_FONT, bold_font_id);
+    page.finish();
+
+    pdf.type1_font(regular_font_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_font_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    let mut content = Content::new();
+
+    // Frame around the page
+    content.set_line_width(2.0);
+    content.rect(
+        MARGIN / 2.0,
+        MARGIN / 2.0,
+        PAGE_WIDTH - MARGIN,
+        PAGE_HEIGHT - MARGIN,
+    );
+    content.stroke();
+
+    let participant = format!("{} {}", certificate.first_name, certificate.last_name);
+    let dates = format!(
+        "{} – {}",
+        certificate.start_date.format("%Y-%m-%d"),
+        certificate.end_date.format("%Y-%m-%d")
+    );
+
+    write_line(&mut content, BOLD_FONT, 36.0, 460.0, "Kursintyg");
+    write_line(&mut content, REGULAR_FONT, 14.0, 410.0, "Detta intygar att");
+    write_line(&mut content, BOLD_FONT, 26.0, 375.0, &participant);
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        14.0,
+        335.0,
+        "har genomfört kursen",
+    );
+    write_line(
+        &mut content,
+        BOLD_FONT,
+        20.0,
+        305.0,
+        &certificate.course_name,
+    );
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        14.0,
+        270.0,
+        &format!("Kursperiod: {}", dates),
+    );
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        14.0,
+        250.0,
+        &format!("Antal kurstillfällen: {}", certificate.sessions),
+    );
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        14.0,
+        180.0,
+        &format!("Utfärdat av {}", certificate.issuer),
+    );
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        14.0,
+        160.0,
+        &format!("Utfärdat {}", certificate.issued_at.format("%Y-%m-%d")),
+    );
+    write_line(
+        &mut content,
+        REGULAR_FONT,
+        10.0,
+        100.0,
+        &format!("Verifieringskod: {}", certificate.code),
+    );
+
+    pdf.stream(content_id, &content.finish());
+
+    return pdf.finish();
+}
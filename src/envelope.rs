@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use actix_web::{http::header, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Every JSON body the API answers with. What was asked for is wrapped in
+// `data`, anything that went wrong in `error`:
+//   {"data": {"id": "…", "category_name": "Arabiska", …}}
+//   {"error": {"message": "Invalid fields!", "fields": {"end_date": ["Cannot be before start_date"]}}}
+// Creating something answers 201 with what was created and a Location header,
+// requests with nothing to answer, like deletes, get an empty 204.
+#[derive(Serialize, ToSchema)]
+pub struct Envelope<T> {
+    pub data: T,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorEnvelope {
+    pub error: ApiError,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiError {
+    pub message: String,
+    // Every failed rule, keyed by the name of the field it applies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, Vec<String>>>,
+    // Known values close to the one that was not recognised
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestions: Option<Vec<String>>,
+}
+
+pub fn data<T: Serialize>(data: T) -> Envelope<T> {
+    return Envelope { data };
+}
+
+pub fn error(message: impl Into<String>) -> ErrorEnvelope {
+    return ErrorEnvelope {
+        error: ApiError {
+            message: message.into(),
+            fields: None,
+            suggestions: None,
+        },
+    };
+}
+
+// 201 with what was created and the path it can be fetched from
+pub fn created<T: Serialize>(location: String, created: T) -> HttpResponse {
+    return HttpResponse::Created()
+        .insert_header((header::LOCATION, location))
+        .json(data(created));
+}
+
+// Paths no route matches
+pub async fn no_such_path() -> HttpResponse {
+    return HttpResponse::NotFound().json(error("No such path!"));
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use serde_json::{json, Value};
+
+    use crate::{
+        tests::{course_request, fixtures, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn wraps_what_was_asked_for_in_data() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+
+        let request = TestRequest::post()
+            .uri("/api/v1/courses")
+            .set_json(course_request(&fixtures, "Arabiska 1"));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers().get(header::LOCATION).unwrap().clone();
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body.as_object().unwrap().len(), 1);
+        assert_eq!(
+            location,
+            format!("/api/v1/courses/{}", body["data"]["id"].as_str().unwrap()).as_str()
+        );
+
+        let request = TestRequest::get().uri("/api/v1/courses");
+        let body: Value = test::call_and_read_body_json(&app, request.to_request()).await;
+        assert_eq!(body["data"][0]["course_name"], "Arabiska 1");
+    }
+
+    #[actix_web::test]
+    async fn wraps_what_went_wrong_in_error() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+
+        let request = TestRequest::get().uri("/api/v1/nothing-here");
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body, json!({ "error": { "message": "No such path!" } }));
+
+        let request = TestRequest::post()
+            .uri("/api/v1/categories")
+            .set_json(json!({ "category_name": "" }));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["error"]["message"], "Invalid fields!");
+        assert!(body["error"]["fields"]["category_name"].is_array());
+    }
+}
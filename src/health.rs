@@ -0,0 +1,167 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    dev::ServerHandle,
+    rt::{signal, time},
+    web::Data,
+    HttpResponse, Responder,
+};
+
+use crate::{
+    models::db::{ComponentStatus, HealthStatus},
+    queries::SCHEMA_VERSION,
+    telemetry::cause_chain,
+    AppState,
+};
+
+// How long /readyz waits for the database before calling it down
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The process is up and answering requests
+pub async fn healthz() -> impl Responder {
+    return HttpResponse::Ok().json(HealthStatus {
+        status: "up",
+        components: vec![],
+    });
+}
+
+// The app can serve requests: it is not shutting down, the database answers and
+// was built from the db_query.sql the queries were written for
+pub async fn readyz(state: Data<AppState>) -> impl Responder {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return HttpResponse::ServiceUnavailable().json(HealthStatus {
+            status: "shutting_down",
+            components: vec![],
+        });
+    }
+
+    let started = Instant::now();
+    let result = time::timeout(DATABASE_TIMEOUT, state.health.get_schema_version()).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let components = match result {
+        Ok(Ok(version)) => vec![
+            ComponentStatus {
+                name: "database",
+                status: "up",
+                latency_ms,
+                detail: None,
+            },
+            ComponentStatus {
+                name: "schema",
+                status: if version == Some(SCHEMA_VERSION) {
+                    "up"
+                } else {
+                    "down"
+                },
+                latency_ms,
+                detail: match version {
+                    Some(version) if version == SCHEMA_VERSION => None,
+                    Some(version) => Some(format!(
+                        "Schema version {}, expected {}",
+                        version, SCHEMA_VERSION
+                    )),
+                    None => Some(format!("No schema version, expected {}", SCHEMA_VERSION)),
+                },
+            },
+        ],
+        Ok(Err(err)) => {
+            tracing::warn!(error = %cause_chain(&err), "Database not ready");
+            vec![ComponentStatus {
+                name: "database",
+                status: "down",
+                latency_ms,
+                detail: Some(String::from("Database unreachable")),
+            }]
+        }
+        Err(_) => vec![ComponentStatus {
+            name: "database",
+            status: "down",
+            latency_ms,
+            detail: Some(String::from("Database did not answer in time")),
+        }],
+    };
+
+    if components.iter().all(|component| component.status == "up") {
+        return HttpResponse::Ok().json(HealthStatus {
+            status: "ready",
+            components,
+        });
+    } else {
+        return HttpResponse::ServiceUnavailable().json(HealthStatus {
+            status: "not_ready",
+            components,
+        });
+    }
+}
+
+// Stops the server on SIGTERM or Ctrl-C. /readyz fails straight away, the server
+// keeps serving for `drain` so the orchestrator stops sending it traffic, then it
+// stops accepting connections and waits for the requests still in flight.
+pub async fn shutdown_on_signal(state: Data<AppState>, server: ServerHandle, drain: Duration) {
+    wait_for_signal().await;
+
+    tracing::info!(drain_seconds = drain.as_secs(), "Shutting down");
+    state.shutting_down.store(true, Ordering::SeqCst);
+    time::sleep(drain).await;
+
+    server.stop(true).await;
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use signal::unix::{signal, SignalKind};
+    use std::{future::poll_fn, task::Poll};
+
+    let mut interrupt = signal(SignalKind::interrupt()).expect("Could not listen for SIGINT");
+    let mut terminate = signal(SignalKind::terminate()).expect("Could not listen for SIGTERM");
+
+    poll_fn(|cx| {
+        if interrupt.poll_recv(cx).is_ready() || terminate.poll_recv(cx).is_ready() {
+            return Poll::Ready(());
+        }
+        return Poll::Pending;
+    })
+    .await;
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    signal::ctrl_c().await.expect("Could not listen for Ctrl-C");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use actix_web::{http::StatusCode, web::Data};
+    use serde_json::json;
+
+    use crate::{
+        tests::{get, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn stops_being_ready_when_shutting_down() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+
+        let (status, body) = get(&app, "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+
+        state.shutting_down.store(true, Ordering::SeqCst);
+
+        let (status, body) = get(&app, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, json!({"status": "shutting_down", "components": []}));
+
+        // Still alive while the requests in flight finish
+        let (status, _) = get(&app, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
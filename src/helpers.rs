@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use crate::models::db::{
+    CategoryTreeNode, CourseAttendanceInfo, Location, NestedCategory, ParticipantAttendance,
+    Subcategory,
+};
+use serde::{Deserialize, Deserializer};
+use uuid::Uuid;
+
+// Builds the attendance row for one participant. The percentage is the share of
+// held sessions the participant was present at, where excused sessions are left
+// out of the count entirely.
+pub fn build_participant_attendance(
+    info: CourseAttendanceInfo,
+    sessions: i32,
+    sessions_held: &[i32],
+    threshold: f64,
+) -> ParticipantAttendance {
+    let mut marks: Vec<Option<String>> = vec![None; sessions.max(0) as usize];
+
+    for (session, status) in info.sessions.iter().zip(info.statuses.iter()) {
+        if let (Some(session), Some(status)) = (session, status) {
+            if *session >= 1 && *session <= sessions {
+                marks[(*session - 1) as usize] = Some(status.clone());
+            }
+        }
+    }
+
+    let mut present = 0;
+    let mut counted = 0;
+
+    for session in sessions_held {
+        match marks[(*session - 1) as usize].as_deref() {
+            Some("present") => {
+                present += 1;
+                counted += 1;
+            }
+            Some("excused") => {}
+            _ => counted += 1,
+        }
+    }
+
+    let attendance_percentage = if counted == 0 {
+        100.0
+    } else {
+        (present as f64 / counted as f64) * 100.0
+    };
+
+    return ParticipantAttendance {
+        booking_id: info.booking_id,
+        first_name: info.first_name,
+        last_name: info.last_name,
+        sessions: marks,
+        attendance_percentage,
+        below_threshold: attendance_percentage < threshold,
+    };
+}
+
+// A session counts as held once anyone on the course has been marked for it
+pub fn sessions_held(infos: &[CourseAttendanceInfo], sessions: i32) -> Vec<i32> {
+    let mut held: Vec<i32> = infos
+        .iter()
+        .flat_map(|info| info.sessions.iter().filter_map(|session| *session))
+        .filter(|session| *session >= 1 && *session <= sessions)
+        .collect();
+
+    held.sort();
+    held.dedup();
+
+    return held;
+}
+
+// Random code printed on certificates, e.g. "3F2A-91C0-7B4E"
+pub fn generate_verification_code() -> String {
+    let random = Uuid::new_v4().simple().to_string().to_uppercase();
+
+    return format!("{}-{}-{}", &random[0..4], &random[4..8], &random[8..12]);
+}
+
+pub fn normalize_verification_code(code: &str) -> String {
+    return code.trim().to_uppercase();
+}
+
+// Lets PATCH bodies tell a missing field (None) apart from an explicit null (Some(None))
+pub fn deserialize_optional_field<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    return Ok(Some(Option::deserialize(deserializer)?));
+}
+
+fn build_subcategories(
+    parent_id: &Uuid,
+    children: &HashMap<Uuid, Vec<&CategoryTreeNode>>,
+) -> Vec<Subcategory> {
+    return children
+        .get(parent_id)
+        .map(|nodes| {
+            nodes
+                .iter()
+                .map(|node| Subcategory {
+                    subcategory_id: Some(node.id),
+                    subcategory_name: Some(node.category_name.clone()),
+                    subcategory_slug: Some(node.slug.clone()),
+                    subcategories: build_subcategories(&node.id, children),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+}
+
+// Nests the flat rows of db.category_tree. Rows are expected in display order,
+// which is kept for every level of the tree.
+pub fn build_category_tree(nodes: &[CategoryTreeNode]) -> Vec<NestedCategory> {
+    let mut children: HashMap<Uuid, Vec<&CategoryTreeNode>> = HashMap::new();
+
+    for node in nodes {
+        if let Some(parent_id) = node.parent_id {
+            children.entry(parent_id).or_default().push(node);
+        }
+    }
+
+    return nodes
+        .iter()
+        .filter(|node| node.parent_id.is_none())
+        .map(|node| NestedCategory {
+            category_id: node.id,
+            category_name: node.category_name.clone(),
+            category_slug: node.slug.clone(),
+            subcategories: build_subcategories(&node.id, &children)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        })
+        .collect();
+}
+
+// Turns a name into a URL slug, e.g. "Språk & Kultur" becomes "sprak-kultur"
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+
+    for c in name.to_lowercase().chars() {
+        let c = match c {
+            'å' | 'ä' | 'à' | 'á' => 'a',
+            'ö' | 'ø' | 'ó' => 'o',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'ü' | 'ú' => 'u',
+            _ => c,
+        };
+
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    return slug.trim_end_matches('-').to_string();
+}
+
+// Picks `base` or the first free "base-2", "base-3"... given the slugs already taken
+pub fn unique_slug(base: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|slug| slug == base) {
+        return base.to_string();
+    }
+
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{}-{}", base, suffix);
+        if !taken.contains(&candidate) {
+            return candidate;
+        }
+        suffix += 1;
+    }
+}
+
+// SCB kommun codes are four digits starting with the two digit code of their län
+pub fn city_code_in_district(city_code: i32, district_code: i32) -> bool {
+    return (100..10000).contains(&city_code) && city_code / 100 == district_code;
+}
+
+// What find_kommun made of the kommun a participant gave
+pub enum KommunMatch<'a> {
+    Found(&'a Location),
+    // Kommuner in different län with the same name, only the code tells them apart
+    Ambiguous(Vec<&'a Location>),
+    Unknown,
+}
+
+// Finds the kommun given either its SCB code ("0180" or "180") or its name.
+// Names are compared by slug, so case, diacritics and punctuation don't matter.
+pub fn find_kommun<'a>(input: &str, kommuner: &'a [Location]) -> KommunMatch<'a> {
+    let input = input.trim();
+
+    let matches: Vec<&Location> = match input.parse::<i32>() {
+        Ok(code) => kommuner
+            .iter()
+            .filter(|kommun| kommun.code == code)
+            .collect(),
+        Err(_) => {
+            let wanted = slugify(input);
+            kommuner
+                .iter()
+                .filter(|kommun| !wanted.is_empty() && slugify(&kommun.name) == wanted)
+                .collect()
+        }
+    };
+
+    return match matches.len() {
+        0 => KommunMatch::Unknown,
+        1 => KommunMatch::Found(matches[0]),
+        _ => KommunMatch::Ambiguous(matches),
+    };
+}
+
+// The kommun as it can be given to find_kommun without being ambiguous
+pub fn kommun_with_code(kommun: &Location) -> String {
+    return format!("{} ({:04})", kommun.name, kommun.code);
+}
+
+// Spells the city a participant gave the way db.locations does, or the postnummer
+// dataset for the postort of their postnummer. Compared by slug like kommuner,
+// anything else is not a city we know of.
+pub fn find_city(input: &str, postort: Option<&str>, kommuner: &[Location]) -> Option<String> {
+    let wanted = slugify(input);
+    if wanted.is_empty() {
+        return None;
+    }
+
+    if let Some(postort) = postort.filter(|postort| slugify(postort) == wanted) {
+        return Some(postort.to_string());
+    }
+
+    return kommuner
+        .iter()
+        .find(|kommun| slugify(&kommun.name) == wanted)
+        .map(|kommun| kommun.name.clone());
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    return previous[b.len()];
+}
+
+// Names of the kommuner closest to what was typed, best match first. Names
+// starting with the input come first, then anything a few typos away.
+pub fn suggest_kommuner(input: &str, kommuner: &[Location], limit: usize) -> Vec<String> {
+    let wanted = slugify(input);
+    if wanted.is_empty() {
+        return vec![];
+    }
+
+    let max_distance = (wanted.len() / 3).max(1);
+
+    let mut candidates: Vec<(usize, &Location)> = kommuner
+        .iter()
+        .filter_map(|kommun| {
+            let slug = slugify(&kommun.name);
+            if slug.starts_with(&wanted) {
+                return Some((0, kommun));
+            }
+
+            let distance = edit_distance(&wanted, &slug);
+            if distance <= max_distance {
+                return Some((distance, kommun));
+            }
+
+            return None;
+        })
+        .collect();
+
+    // Stable sort keeps the alphabetical order of the input within each distance
+    candidates.sort_by_key(|(distance, _)| *distance);
+
+    return candidates
+        .into_iter()
+        .take(limit)
+        .map(|(_, kommun)| kommun.name.clone())
+        .collect();
+}
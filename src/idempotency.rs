@@ -0,0 +1,352 @@
+use std::{
+    error::Error as StdError,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, Method, StatusCode},
+    web::{Bytes, Data},
+    Error, HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    envelope,
+    telemetry::{cause_chain, internal_error},
+    AppState,
+};
+
+pub const KEY_HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+// Makes POSTs to a resource safe to retry. A request sent with an
+// Idempotency-Key header is handled once, retries with the same key and body
+// get the stored response again, marked with Idempotent-Replayed, for as long
+// as AppState::idempotency_ttl. Reusing a key for a different body is a 409.
+// Keys are not tied to the client's address, which changes when a phone
+// retries over another network.
+// Server errors, rate limits and failed bot checks are not stored, retrying
+// those handles the request again.
+pub struct Idempotent {
+    // Keys are only compared within an operation, so an old path and its
+    // /api/v1 counterpart share them
+    operation: &'static str,
+}
+
+impl Idempotent {
+    pub fn new(operation: &'static str) -> Idempotent {
+        return Idempotent { operation };
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotent
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = IdempotentMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        return ready(Ok(IdempotentMiddleware {
+            service: Rc::new(service),
+            operation: self.operation,
+        }));
+    }
+}
+
+pub struct IdempotentMiddleware<S> {
+    service: Rc<S>,
+    operation: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotentMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        return Box::pin(handle(self.operation, req, Rc::clone(&self.service)));
+    }
+}
+
+async fn handle<S, B>(
+    operation: &'static str,
+    mut req: ServiceRequest,
+    service: Rc<S>,
+) -> Result<ServiceResponse<BoxBody>, Error>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody + 'static,
+{
+    let state = req.app_data::<Data<AppState>>().cloned();
+    let key = req
+        .headers()
+        .get(KEY_HEADER)
+        .map(|key| key.to_str().map(str::to_string));
+
+    let (state, key) = match (state, key) {
+        (Some(state), Some(key)) if req.method() == Method::POST => (state, key),
+        _ => return Ok(service.call(req).await?.map_into_boxed_body()),
+    };
+
+    let key = match key {
+        Ok(key) if (1..=255).contains(&key.len()) => key,
+        _ => {
+            return Ok(
+                req.into_response(HttpResponse::BadRequest().json(envelope::error(
+                    "Idempotency-Key must be 1 to 255 visible characters!",
+                ))),
+            )
+        }
+    };
+
+    // The body is read here to tell retries from other requests, then put back
+    // for the handler
+    let body = req.extract::<Bytes>().await?;
+    let request_hash = format!("{:x}", Sha256::digest(&body));
+    req.set_payload(payload(body));
+
+    let now = Utc::now();
+    let claimed = state
+        .idempotency
+        .claim_idempotency_key(
+            operation,
+            &key,
+            &request_hash,
+            &before(now, state.idempotency_ttl),
+            &before(now, state.http.request_timeout),
+        )
+        .await;
+
+    match claimed {
+        Ok(None) => {}
+        Ok(Some(stored)) => {
+            let response = match (
+                stored.request_hash == request_hash,
+                stored.status,
+                stored.body,
+            ) {
+                (false, _, _) => HttpResponse::Conflict().json(envelope::error(
+                    "Idempotency-Key was already used for a different request!",
+                )),
+                (true, Some(status), Some(body)) => replay(status, body, stored.location),
+                (true, _, _) => HttpResponse::Conflict().json(envelope::error(
+                    "A request with this Idempotency-Key is still being handled!",
+                )),
+            };
+            return Ok(req.into_response(response));
+        }
+        Err(err) => {
+            return Ok(req.into_response(internal_error("Error checking Idempotency-Key", &err)))
+        }
+    }
+
+    let response = match service.call(req).await {
+        Ok(response) => response,
+        Err(err) => {
+            release(&state, operation, &key).await;
+            return Err(err);
+        }
+    };
+
+    if !worth_storing(response.status()) {
+        release(&state, operation, &key).await;
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let (request, response) = response.into_parts();
+    let (response, body) = response.into_parts();
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string);
+    let body = match body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            release(&state, operation, &key).await;
+            let err: Box<dyn StdError> = err.into();
+            let response = internal_error("Error reading the response", &*err);
+            return Ok(ServiceResponse::new(request, response));
+        }
+    };
+
+    let stored = state
+        .idempotency
+        .store_idempotent_response(
+            operation,
+            &key,
+            response.status().as_u16() as i32,
+            &String::from_utf8_lossy(&body),
+            location.as_deref(),
+        )
+        .await;
+    if let Err(err) = stored {
+        tracing::error!(error = %cause_chain(&err), "Error storing idempotent response");
+    }
+
+    return Ok(ServiceResponse::new(
+        request,
+        response.set_body(body).map_into_boxed_body(),
+    ));
+}
+
+// Answers that depend on more than the request itself are worth trying again
+fn worth_storing(status: StatusCode) -> bool {
+    return !(status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::FORBIDDEN);
+}
+
+// Every stored response is JSON, created resources keep their Location
+fn replay(status: i32, body: String, location: Option<String>) -> HttpResponse {
+    let status = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+
+    let mut response = HttpResponse::build(status);
+    if let Some(location) = location {
+        response.insert_header((header::LOCATION, location));
+    }
+
+    return response
+        .content_type("application/json")
+        .insert_header((REPLAYED_HEADER, "true"))
+        .body(body);
+}
+
+async fn release(state: &AppState, operation: &str, key: &str) {
+    if let Err(err) = state
+        .idempotency
+        .release_idempotency_key(operation, key)
+        .await
+    {
+        tracing::error!(error = %cause_chain(&err), "Error releasing Idempotency-Key");
+    }
+}
+
+fn before(now: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    return now - chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero());
+}
+
+fn payload(body: Bytes) -> Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(body);
+
+    return payload.into();
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use serde_json::Value;
+
+    use crate::{
+        tests::{add_course, booking_request, course_request, fixtures, send, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn replays_retried_bookings() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        let request = || {
+            return TestRequest::post()
+                .uri("/api/v1/bookings")
+                .insert_header(("Idempotency-Key", "booking-1"))
+                .set_json(booking_request(&course_id, 199001011239));
+        };
+
+        let first = test::call_service(&app, request().to_request()).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get("Idempotent-Replayed").is_none());
+
+        let retry = test::call_service(&app, request().to_request()).await;
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers().get("Idempotent-Replayed").unwrap(), "true");
+        let body: Value = test::read_body_json(retry).await;
+        assert_eq!(body["data"]["course_id"], course_id.as_str());
+
+        // The retry never reached the handler
+        assert_eq!(state.metrics.bookings_created.get(), 1);
+        assert_eq!(state.metrics.bookings_duplicate.get(), 0);
+    }
+
+    #[actix_web::test]
+    async fn rejects_reusing_a_key_for_another_request() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        let request = |personal_number: i64| {
+            return TestRequest::post()
+                .uri("/api/v1/bookings")
+                .insert_header(("Idempotency-Key", "booking-1"))
+                .set_json(booking_request(&course_id, personal_number));
+        };
+
+        let (status, _) = send(&app, request(199001011239)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(&app, request(198502023453)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            body["message"],
+            "Idempotency-Key was already used for a different request!"
+        );
+    }
+
+    #[actix_web::test]
+    async fn replays_retries_from_another_address() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        let request = |client: &str, personal_number: i64| {
+            return TestRequest::post()
+                .uri("/api/v1/bookings")
+                .peer_addr(client.parse().unwrap())
+                .insert_header(("Idempotency-Key", "booking-1"))
+                .set_json(booking_request(&course_id, personal_number));
+        };
+
+        let (status, _) = send(&app, request("10.0.0.1:40000", 199001011239)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        // A phone moving from wifi to mobile data retries from a new address
+        let response =
+            test::call_service(&app, request("10.0.0.2:40000", 199001011239).to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get("Idempotent-Replayed").unwrap(),
+            "true"
+        );
+        assert_eq!(state.metrics.bookings_created.get(), 1);
+
+        let (status, _) = send(&app, request("10.0.0.3:40000", 198502023453)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,240 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder,
+    imageops::FilterType,
+    io::{Limits, Reader},
+    DynamicImage, ImageFormat, ImageOutputFormat,
+};
+use sha2::{Digest, Sha256};
+
+// Images wider or taller than this are refused before they are decoded. Course
+// images are shown at most 1200 pixels wide, anything larger only costs memory.
+const MAX_DIMENSION: u32 = 4000;
+
+// The most a decode may allocate, enough for a MAX_DIMENSION square in RGBA.
+// A small, highly compressed upload can otherwise decode to hundreds of MB.
+const MAX_ALLOC: u64 = MAX_DIMENSION as u64 * MAX_DIMENSION as u64 * 4;
+
+const JPEG_QUALITY: u8 = 85;
+
+// The sizes an uploaded image is kept in besides the original, named by the
+// longest side they are scaled down to. Smaller images are not scaled up.
+pub const SIZES: [(&str, u32); 2] = [("large", 1200), ("thumbnail", 320)];
+
+// One of the files an upload is stored as
+pub struct Variant {
+    pub name: &'static str,
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+impl Variant {
+    pub fn file_name(&self) -> String {
+        return format!("{}.{}", self.name, extension(self.format));
+    }
+}
+
+pub struct ProcessedImage {
+    // Start of the SHA-256 of the upload, so a new image gets new URLs
+    pub hash: String,
+    pub variants: Vec<Variant>,
+}
+
+// Why an upload was refused
+#[derive(Debug, PartialEq)]
+pub enum ImageError {
+    // Not a JPEG, PNG or WebP, or not the type it was sent as
+    Unsupported,
+    // The type was right but the image could not be read
+    Unreadable(String),
+}
+
+pub fn content_type(format: ImageFormat) -> &'static str {
+    return match format {
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Png => "image/png",
+        ImageFormat::WebP => "image/webp",
+        _ => "application/octet-stream",
+    };
+}
+
+fn extension(format: ImageFormat) -> &'static str {
+    return match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        _ => "bin",
+    };
+}
+
+fn accepted(content_type: &str) -> Option<ImageFormat> {
+    return match content_type {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/png" => Some(ImageFormat::Png),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    };
+}
+
+// Checks that `bytes` is an image of the type it was uploaded as and scales it
+// to every size in SIZES. The original is kept as it was sent. Scaled PNGs stay
+// PNGs to keep their transparency, everything else becomes JPEG.
+pub fn process(bytes: &[u8], sent_as: &str) -> Result<ProcessedImage, ImageError> {
+    let format = accepted(sent_as).ok_or(ImageError::Unsupported)?;
+    if image::guess_format(bytes).ok() != Some(format) {
+        return Err(ImageError::Unsupported);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+
+    let mut reader = Reader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|err| ImageError::Unreadable(err.to_string()))?;
+
+    let mut variants = vec![Variant {
+        name: "original",
+        bytes: bytes.to_vec(),
+        format,
+    }];
+    for (name, longest_side) in SIZES {
+        variants.push(scaled(&image, name, longest_side, format)?);
+    }
+
+    let hash = format!("{:x}", Sha256::digest(bytes));
+
+    return Ok(ProcessedImage {
+        hash: hash[..16].to_string(),
+        variants,
+    });
+}
+
+fn scaled(
+    image: &DynamicImage,
+    name: &'static str,
+    longest_side: u32,
+    format: ImageFormat,
+) -> Result<Variant, ImageError> {
+    let image = if image.width() > longest_side || image.height() > longest_side {
+        image.resize(longest_side, longest_side, FilterType::Lanczos3)
+    } else {
+        image.clone()
+    };
+
+    let mut bytes = vec![];
+    let format = match format {
+        ImageFormat::Png => {
+            image
+                .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+                .map_err(|err| ImageError::Unreadable(err.to_string()))?;
+            ImageFormat::Png
+        }
+        _ => {
+            JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+                .encode_image(&image.to_rgb8())
+                .map_err(|err| ImageError::Unreadable(err.to_string()))?;
+            ImageFormat::Jpeg
+        }
+    };
+
+    return Ok(Variant {
+        name,
+        bytes,
+        format,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, ImageOutputFormat, RgbaImage};
+
+    use super::{process, ImageError};
+
+    fn encoded(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+        let mut bytes = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        return bytes;
+    }
+
+    #[test]
+    fn scales_images_down_to_every_size() {
+        let png = encoded(2400, 1200, ImageOutputFormat::Png);
+        let processed = process(&png, "image/png").unwrap();
+
+        let sizes: Vec<(String, (u32, u32))> = processed
+            .variants
+            .iter()
+            .map(|variant| {
+                let image = image::load_from_memory(&variant.bytes).unwrap();
+                return (variant.file_name(), (image.width(), image.height()));
+            })
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (String::from("original.png"), (2400, 1200)),
+                (String::from("large.png"), (1200, 600)),
+                (String::from("thumbnail.png"), (320, 160)),
+            ]
+        );
+        assert_eq!(processed.variants[0].bytes, png);
+        assert_eq!(processed.hash.len(), 16);
+
+        // Small images keep their size, as JPEGs unless they were PNGs
+        let jpeg = encoded(100, 50, ImageOutputFormat::Jpeg(90));
+        let processed = process(&jpeg, "image/jpeg").unwrap();
+        let thumbnail = &processed.variants[2];
+        assert_eq!(thumbnail.format, ImageFormat::Jpeg);
+        assert_eq!(
+            image::load_from_memory(&thumbnail.bytes).unwrap().width(),
+            100
+        );
+    }
+
+    #[test]
+    fn refuses_anything_but_the_image_it_claims_to_be() {
+        let png = encoded(10, 10, ImageOutputFormat::Png);
+
+        assert_eq!(
+            process(&png, "image/jpeg").err(),
+            Some(ImageError::Unsupported)
+        );
+        assert_eq!(
+            process(&png, "image/gif").err(),
+            Some(ImageError::Unsupported)
+        );
+        assert_eq!(
+            process(b"<svg></svg>", "image/png").err(),
+            Some(ImageError::Unsupported)
+        );
+        assert!(matches!(
+            process(&png[..png.len() / 2], "image/png"),
+            Err(ImageError::Unreadable(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_images_larger_than_course_images_need() {
+        // However well they compress, they are refused before they are decoded
+        for (width, height) in [(4001, 10), (10, 4001)] {
+            let png = encoded(width, height, ImageOutputFormat::Png);
+            assert!(matches!(
+                process(&png, "image/png"),
+                Err(ImageError::Unreadable(_))
+            ));
+        }
+
+        let png = encoded(4000, 10, ImageOutputFormat::Png);
+        assert!(process(&png, "image/png").is_ok());
+    }
+}
@@ -1,61 +1,239 @@
+use actix_multipart::form::MultipartFormConfig;
 use dotenv::dotenv;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-use std::{ops::Deref, sync::Mutex};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions,
+};
+use std::{ops::Deref, str::FromStr, sync::{atomic::AtomicBool, Mutex}, time::Duration};
 
 use actix_web::{
     get, post,
-    web::{scope, Data},
-    App, HttpResponse, HttpServer, Responder,
+    web::{self, scope, Data, JsonConfig, PathConfig, QueryConfig, ServiceConfig},
+    HttpResponse, HttpServer, Responder,
 };
 
+mod blobstore;
+mod botcheck;
+mod cache;
+mod certificate;
+mod envelope;
+mod health;
 mod helpers;
+mod idempotency;
+mod images;
+mod metrics;
 mod models;
+mod openapi;
+mod postnummer;
+mod ratelimit;
+mod repos;
+mod security;
+mod seed;
 mod services;
+mod telemetry;
+mod validation;
+
+#[cfg(test)]
+mod tests;
 
 pub mod queries;
 
-use services::{
-    create_booking, create_category, create_city, create_course, create_district, create_subcategory,
-    get_categories_all, get_cities_by_district, get_courses_all, get_courses_by_id,
-    get_district_all, get_subcategories_by_category_id, get_locations_all, get_courses_with_locations
-};
 use load_dotenv::load_dotenv;
+use metrics::Metrics;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+use postnummer::{DatasetLookup, PostnummerLookup};
+use openapi::ApiDoc;
+use blobstore::BlobStore;
+use botcheck::BotCheck;
+use cache::CatalogueCache;
+use ratelimit::BookingLimits;
+use security::HttpPolicy;
+use repos::{
+    postgres::PgRepo, BookingRepo, CategoryRepo, CourseRepo, HealthRepo, IdempotencyRepo,
+    LocationRepo,
+};
+use seed::seed_locations;
+use validation::{
+    json_error_handler, multipart_error_handler, path_error_handler, query_error_handler,
+};
 
 pub struct AppState {
-    db: Pool<Postgres>,
+    // Courses with their cities and subcategories
+    courses: Box<dyn CourseRepo>,
+    // Bookings, attendance and certificates
+    bookings: Box<dyn BookingRepo>,
+    // Districts (län) and cities (kommuner)
+    locations: Box<dyn LocationRepo>,
+    // Categories and subcategories
+    categories: Box<dyn CategoryRepo>,
+    // Database reachability and schema, for /readyz
+    health: Box<dyn HealthRepo>,
+    // Responses kept for requests sent with an Idempotency-Key
+    idempotency: Box<dyn IdempotencyRepo>,
+    // Minimum attendance percentage required for CSN and certificates
+    attendance_threshold: f64,
+    // Organisation name printed on course certificates
+    certificate_issuer: String,
+    // How long responses to requests with an Idempotency-Key are replayed
+    idempotency_ttl: Duration,
+    // Finds the postort of a postnummer, backed by the bundled dataset
+    postnummer_lookup: Box<dyn PostnummerLookup>,
+    // How often bookings may be attempted per address and per person
+    booking_limits: BookingLimits,
+    // Tells bookings made by people from those made by scripts
+    bot_check: Box<dyn BotCheck>,
+    // Course, category and location responses, with their ETags
+    catalogue: CatalogueCache,
+    // Uploaded course images in every size they are kept in
+    blobs: Box<dyn BlobStore>,
+    // Request, pool and booking metrics served on /metrics
+    metrics: Metrics,
+    // CORS, body size and timeout limits for requests
+    http: HttpPolicy,
+    // Set once a shutdown signal arrives, so /readyz fails while requests drain
+    shutting_down: AtomicBool,
+}
+
+// The app with its middleware, as served by main and tested by the tests
+macro_rules! app {
+    ($state:expr) => {{
+        let state: actix_web::web::Data<crate::AppState> = $state;
+
+        actix_web::App::new()
+            .app_data(state.clone())
+            .wrap_fn(crate::security::request_timeout)
+            .wrap_fn(crate::metrics::track_requests)
+            .wrap_fn(crate::telemetry::request_id_header)
+            .wrap(tracing_actix_web::TracingLogger::default())
+            .wrap(crate::security::security_headers())
+            .wrap(crate::security::cors(&state.http))
+            .configure(|cfg| crate::app_config(cfg, &state.http))
+    }};
+}
+
+#[cfg(test)]
+pub(crate) use app;
+
+// Everything the app serves
+fn app_config(cfg: &mut ServiceConfig, http: &HttpPolicy) {
+    cfg.app_data(
+        JsonConfig::default()
+            .limit(http.json_limit)
+            .error_handler(json_error_handler),
+    )
+        .app_data(
+            MultipartFormConfig::default()
+                .total_limit(http.image_limit)
+                .memory_limit(http.image_limit)
+                .error_handler(multipart_error_handler(http.image_limit)),
+        )
+        .app_data(PathConfig::default().error_handler(path_error_handler))
+        .app_data(QueryConfig::default().error_handler(query_error_handler))
+        .service(scope("/api/v1").configure(services::config))
+        .service(web::resource("/healthz").route(web::get().to(health::healthz)))
+        .service(web::resource("/readyz").route(web::get().to(health::readyz)))
+        .service(web::resource("/metrics").route(web::get().to(metrics::get_metrics)))
+        .service(web::resource("/docs").to(|| async {
+            HttpResponse::PermanentRedirect()
+                .insert_header(("Location", "/docs/"))
+                .finish()
+        }))
+        .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", ApiDoc::openapi()))
+        .configure(services::legacy::config)
+        .default_service(web::to(envelope::no_such_path));
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // dotenv().ok();
     load_dotenv!();
+    telemetry::init();
+
     let database_url = env!("DB_URL");
     //let database_url = std::env::var("DB_URL").expect("DATABASE_URL must be set");
+    let mut connect_options =
+        PgConnectOptions::from_str(&database_url).expect("Invalid database url");
+    connect_options
+        .log_statements(log::LevelFilter::Debug)
+        .log_slow_statements(log::LevelFilter::Warn, Duration::from_millis(500));
+
     let pool = PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect_with(connect_options)
         .await
         .expect("Error building a connection pool");
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(Data::new(AppState { db: pool.clone() }))
-            .service(create_booking)
-            .service(create_course)
-            .service(create_category)
-            .service(create_subcategory)
-            .service(create_district)
-            .service(create_city)
-            .service(get_courses_all)
-            .service(get_courses_by_id)
-            .service(get_categories_all)
-            .service(get_district_all)
-            .service(get_cities_by_district)
-            .service(get_subcategories_by_category_id)
-            .service(get_locations_all)
-            .service(get_courses_with_locations)
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    let attendance_threshold: f64 = std::env::var("ATTENDANCE_THRESHOLD")
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .unwrap_or(80.0);
+
+    let certificate_issuer = std::env::var("CERTIFICATE_ISSUER")
+        .unwrap_or_else(|_| String::from("Ibn Rushd Studieförbund"));
+
+    let idempotency_ttl = std::env::var("IDEMPOTENCY_TTL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map_or(Duration::from_secs(24 * 60 * 60), Duration::from_secs);
+
+    // Seconds between /readyz failing and the server no longer accepting connections
+    let shutdown_drain = std::env::var("SHUTDOWN_DRAIN_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map_or(Duration::from_secs(5), Duration::from_secs);
+
+    let metrics = Metrics::new(Some(pool.clone()));
+    let repo = PgRepo::new(pool);
+
+    let state = Data::new(AppState {
+        courses: Box::new(repo.clone()),
+        bookings: Box::new(repo.clone()),
+        locations: Box::new(repo.clone()),
+        categories: Box::new(repo.clone()),
+        health: Box::new(repo.clone()),
+        idempotency: Box::new(repo),
+        attendance_threshold,
+        certificate_issuer,
+        idempotency_ttl,
+        postnummer_lookup: Box::new(DatasetLookup::bundled()),
+        booking_limits: BookingLimits::from_env(),
+        bot_check: botcheck::from_env(),
+        catalogue: CatalogueCache::from_env(),
+        blobs: blobstore::from_env(),
+        metrics,
+        http: HttpPolicy::from_env(),
+        shutting_down: AtomicBool::new(false),
+    });
+
+    // `ibnrushd-api seed-locations` fills db.locations with all län and kommun
+    if std::env::args().nth(1).as_deref() == Some("seed-locations") {
+        match seed_locations(&state).await {
+            Ok(summary) => tracing::info!(
+                created = summary.created,
+                updated = summary.updated,
+                unchanged = summary.unchanged,
+                "Seeded locations"
+            ),
+            Err(err) => {
+                tracing::error!(error = %telemetry::cause_chain(&err), "Error seeding locations");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let app_state = state.clone();
+    let server = HttpServer::new(move || app!(app_state.clone()))
+        .disable_signals()
+        .bind(("127.0.0.1", 8080))?
+        .run();
+
+    actix_web::rt::spawn(health::shutdown_on_signal(
+        state,
+        server.handle(),
+        shutdown_drain,
+    ));
+
+    server.await
 }
@@ -0,0 +1,270 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    rt::time::timeout,
+    web::Data,
+    Error, HttpResponse, Responder,
+};
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::AppState;
+
+// Everything exported on /metrics, in a registry of its own per app
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    // Not set when running without a database, e.g. in the in-memory tests
+    pool: Option<PgPool>,
+    pool_connections: IntGauge,
+    pool_idle_connections: IntGauge,
+    pool_acquire_wait: Gauge,
+    pub bookings_created: IntCounter,
+    pub bookings_rejected_full: IntCounter,
+    pub bookings_duplicate: IntCounter,
+    pub bookings_rate_limited: IntCounter,
+    pub bookings_failed_bot_check: IntCounter,
+}
+
+impl Metrics {
+    pub fn new(pool: Option<PgPool>) -> Metrics {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle HTTP requests",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Connections currently open in the database pool",
+        )
+        .unwrap();
+        let pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Open connections in the database pool not in use",
+        )
+        .unwrap();
+        let pool_acquire_wait = Gauge::new(
+            "db_pool_acquire_wait_seconds",
+            "Time the last scrape waited for a connection from the database pool",
+        )
+        .unwrap();
+        let bookings_created = IntCounter::new("bookings_created_total", "Bookings made").unwrap();
+        let bookings_rejected_full = IntCounter::new(
+            "bookings_rejected_full_total",
+            "Bookings rejected because the course was fully booked",
+        )
+        .unwrap();
+        let bookings_duplicate = IntCounter::new(
+            "bookings_duplicate_total",
+            "Attempts to book a course the participant had already booked",
+        )
+        .unwrap();
+        let bookings_rate_limited = IntCounter::new(
+            "bookings_rate_limited_total",
+            "Booking attempts refused for coming too often from one address or person",
+        )
+        .unwrap();
+        let bookings_failed_bot_check = IntCounter::new(
+            "bookings_failed_bot_check_total",
+            "Booking attempts refused for missing or invalid proof of being a person",
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_idle_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_acquire_wait.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bookings_created.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bookings_rejected_full.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bookings_duplicate.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bookings_rate_limited.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(bookings_failed_bot_check.clone()))
+            .unwrap();
+
+        return Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            pool,
+            pool_connections,
+            pool_idle_connections,
+            pool_acquire_wait,
+            bookings_created,
+            bookings_rejected_full,
+            bookings_duplicate,
+            bookings_rate_limited,
+            bookings_failed_bot_check,
+        };
+    }
+
+    // The pool has no wait statistics of its own, so each scrape times how long
+    // it takes to get a connection, giving up after a second
+    async fn update_pool_stats(&self) {
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => return,
+        };
+
+        let started = Instant::now();
+        let waited = match timeout(Duration::from_secs(1), pool.acquire()).await {
+            Ok(Ok(_)) => started.elapsed(),
+            _ => Duration::from_secs(1),
+        };
+
+        self.pool_connections.set(pool.size() as i64);
+        self.pool_idle_connections.set(pool.num_idle() as i64);
+        self.pool_acquire_wait.set(waited.as_secs_f64());
+    }
+
+    // Everything in the Prometheus text format
+    pub async fn render(&self) -> String {
+        self.update_pool_stats().await;
+
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+
+        return String::from_utf8(buffer).unwrap();
+    }
+}
+
+// Counts every request and how long it took by method, route and status. Routes
+// are the patterns they were matched by, e.g. /api/v1/courses/{id}. Requests
+// failing in middleware, such as timeouts, have lost their route by then.
+pub fn track_requests<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let started = Instant::now();
+    let state = req.app_data::<Data<AppState>>().cloned();
+    let method = req.method().to_string();
+    let response = srv.call(req);
+
+    return async move {
+        let response = response.await;
+
+        if let Some(state) = state {
+            let (route, status) = match &response {
+                Ok(response) => (
+                    response
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| String::from("unmatched")),
+                    response.status(),
+                ),
+                Err(err) => (
+                    String::from("unknown"),
+                    err.as_response_error().status_code(),
+                ),
+            };
+            let status = status.as_u16().to_string();
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+
+            state.metrics.http_requests.with_label_values(&labels).inc();
+            state
+                .metrics
+                .http_request_duration
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+        }
+
+        return response;
+    };
+}
+
+pub async fn get_metrics(state: Data<AppState>) -> impl Responder {
+    return HttpResponse::Ok()
+        .content_type(TextEncoder::new().format_type())
+        .body(state.metrics.render().await);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use serde_json::json;
+
+    use crate::{
+        tests::{add_course, book, booking_request, course_request, fixtures, post, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn counts_requests_and_bookings() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+
+        let mut course = course_request(&fixtures, "Arabiska 1");
+        course["max_seats"] = json!(1);
+        let course_id = add_course(&app, course).await;
+
+        book(&app, &course_id, 199001011239).await;
+        let (status, _) = post(
+            &app,
+            "/api/v1/bookings",
+            booking_request(&course_id, 199001011239),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let response =
+            test::call_service(&app, TestRequest::get().uri("/metrics").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = test::read_body(response).await;
+        let metrics = std::str::from_utf8(&body).unwrap();
+
+        assert!(metrics.contains("bookings_created_total 1\n"));
+        assert!(metrics.contains("bookings_rejected_full_total 1\n"));
+        assert!(metrics.contains(
+            r#"http_requests_total{method="POST",route="/api/v1/bookings",status="201"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"http_request_duration_seconds_count{method="POST",route="/api/v1/bookings",status="409"} 1"#
+        ));
+    }
+}
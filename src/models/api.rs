@@ -1,62 +1,177 @@
+use actix_multipart::form::{bytes::Bytes, MultipartForm};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-use super::db::{Location, Category};
+use crate::{helpers::deserialize_optional_field, postnummer::Postnummer, validation};
 
-#[derive(Deserialize)]
+use super::{
+    db::{Location, Category},
+    ids::{BookingId, CategoryId, CourseId, LocationId},
+};
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validation::course_dates", skip_on_field_errors = false))]
 pub struct CreateCourseRequest {
+    #[validate(custom = "validation::not_blank")]
     pub course_name: String,
     pub course_description: String,
+    #[validate(custom = "validation::rfc3339_date")]
     pub start_date: String,
+    #[validate(custom = "validation::rfc3339_date")]
     pub end_date: String,
     pub csn_entitled: bool,
+    #[validate(range(min = 1))]
     pub max_seats: i32,
     pub image: String,
     pub days: String,
     pub hours: String,
+    #[validate(range(min = 0))]
     pub price: i32,
+    #[validate(range(min = 1))]
     pub sessions: i32,
     pub visible: bool,
-    pub city_ids: Vec<Uuid>,
-    pub subcategory_ids: Vec<Uuid>,
+    pub city_ids: Vec<LocationId>,
+    pub subcategory_ids: Vec<CategoryId>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct CreateDistrictRequest {
+    #[validate(custom = "validation::not_blank")]
     pub name: String,
+    // SCB län code
+    #[validate(range(min = 1, max = 99))]
     pub code: i32,
+    #[serde(default)]
+    pub sort_order: i32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct CreateCityRequest {
+    #[validate(custom = "validation::not_blank")]
     pub name: String,
-    pub district_id: String,
+    pub district_id: LocationId,
+    // SCB kommun code
+    #[validate(range(min = 100, max = 9999))]
     pub code: i32,
+    #[serde(default)]
+    pub sort_order: i32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct CreateSubcategoryRequest {
+    #[validate(custom = "validation::not_blank")]
     pub category_name: String,
-    pub parent_id: String,
+    pub parent_id: CategoryId,
+    #[serde(default)]
+    pub sort_order: i32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct CreateCategoryRequest {
+    #[validate(custom = "validation::not_blank")]
     pub category_name: String,
+    #[serde(default)]
+    pub sort_order: i32,
 }
 
 
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct CreateBookingRequest {
+    #[validate(custom = "validation::personal_number")]
     pub personal_number: i64,
+    #[validate(custom = "validation::not_blank")]
     pub first_name: String,
+    #[validate(custom = "validation::not_blank")]
     pub last_name: String,
+    #[validate(custom = "validation::not_blank")]
     pub address: String,
-    pub zipcode: i32,
+    #[schema(value_type = String, example = "113 30")]
+    pub zipcode: Postnummer,
+    // The postort of the postnummer or a kommun name. May be left empty, it is
+    // then filled in from the postnummer.
+    #[serde(default)]
     pub city: String,
+    // SCB code or name of a kommun in db.locations
+    #[validate(custom = "validation::not_blank")]
     pub kommun: String,
+    #[validate(email)]
     pub email: String,
+    // Stored in E.164 form once validated
+    #[validate(custom = "validation::swedish_mobile")]
     pub mobile: String,
-    pub course_id: Uuid,
-}
\ No newline at end of file
+    pub course_id: CourseId,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AttendanceStatus {
+    Present,
+    Absent,
+    Excused,
+}
+
+impl AttendanceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttendanceStatus::Present => "present",
+            AttendanceStatus::Absent => "absent",
+            AttendanceStatus::Excused => "excused",
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AttendanceEntry {
+    pub booking_id: BookingId,
+    pub status: AttendanceStatus,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MarkAttendanceRequest {
+    pub session: i32,
+    pub entries: Vec<AttendanceEntry>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct UpdateCategoryRequest {
+    #[validate(custom = "validation::not_blank")]
+    pub category_name: Option<String>,
+    // Absent leaves the parent unchanged, null turns the category into a top level category
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[schema(value_type = Option<CategoryId>)]
+    pub parent_id: Option<Option<CategoryId>>,
+    pub sort_order: Option<i32>,
+    // Slugs are kept when a category is renamed so that URLs stay stable
+    #[validate(custom = "validation::slug")]
+    pub slug: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteCategoryQuery {
+    // Subcategory that takes over the courses of the deleted categories
+    pub reassign_to: Option<CategoryId>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct UpdateLocationRequest {
+    #[validate(custom = "validation::not_blank")]
+    pub name: Option<String>,
+    // Within the range of the kind of location, as when it was created
+    #[validate(range(min = 1, max = 9999))]
+    pub code: Option<i32>,
+    // Moves a city to another district, not allowed for districts
+    pub district_id: Option<LocationId>,
+    pub sort_order: Option<i32>,
+    #[validate(custom = "validation::slug")]
+    pub slug: Option<String>,
+}
+
+// A course image, sent as multipart/form-data in the field "image"
+#[derive(MultipartForm, ToSchema)]
+pub struct ImageUpload {
+    #[schema(value_type = String, format = Binary)]
+    pub image: Bytes,
+}
@@ -4,9 +4,12 @@ use sqlx::{
     self,
     types::chrono::Utc,
 };
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(sqlx::FromRow, Serialize)]
+use crate::postnummer::Postnummer;
+
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
 pub struct Course {
     pub id: Uuid,
     pub course_name: String,
@@ -59,12 +62,14 @@ pub struct CourseBooking {
     paid: bool,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Location {
-    id: Uuid,
-    name: String,
-    parent_id: Option<Uuid>,
-    code: i32,
+    pub id: Uuid,
+    pub name: String,
+    pub parent_id: Option<Uuid>,
+    pub code: i32,
+    pub sort_order: i32,
+    pub slug: String,
 }
 
 #[derive(sqlx::FromRow)]
@@ -74,57 +79,187 @@ pub struct CourseLocation {
     location_id: i32,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct Category {
-    id: Uuid,
-    category_name: String,
-    parent_id: Option<Uuid>,
+    pub id: Uuid,
+    pub category_name: String,
+    pub parent_id: Option<Uuid>,
+    pub sort_order: i32,
+    pub slug: String,
 }
 
 #[derive(sqlx::FromRow, Serialize)]
 pub struct DistrictCities {
     pub district_id: Uuid,
     pub district_name: String,
+    pub district_slug: String,
+    pub sort_order: i32,
     pub cities_id: Vec<Option<Uuid>>,
     pub cities_name: Vec<Option<String>>,
+    pub cities_slug: Vec<Option<String>>,
 }
 
-#[derive(sqlx::FromRow, Serialize)]
-pub struct CategorySubcategories {
-    pub category_id: Uuid,
+#[derive(sqlx::FromRow)]
+pub struct CategoryTreeNode {
+    pub id: Uuid,
     pub category_name: String,
-    pub subcategory_ids: Vec<Option<Uuid>>,
-    pub subcategory_names: Vec<Option<String>>,
+    pub slug: String,
+    pub parent_id: Option<Uuid>,
+    pub sort_order: i32,
+    pub depth: i32,
+    pub path: Vec<Uuid>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Subcategory {
     pub subcategory_id: Option<Uuid>,
     pub subcategory_name: Option<String>,
+    pub subcategory_slug: Option<String>,
+    #[schema(no_recursion)]
+    pub subcategories: Vec<Subcategory>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct NestedCategory {
     pub category_id: Uuid,
     pub category_name: String,
+    pub category_slug: String,
     pub subcategories: Vec<Option<Subcategory>>
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct City {
     pub city_id: Option<Uuid>,
     pub city_name: Option<String>,
+    pub city_slug: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct District {
     pub district_id: Uuid,
     pub district_name: String,
+    pub district_slug: String,
     pub cities: Vec<Option<City>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CoursesCategoriesDistricts {
     pub courses: Vec<Course>,
     pub categories: Vec<NestedCategory>,
     pub districts: Vec<District>,
 }
+
+#[derive(sqlx::FromRow)]
+pub struct CourseAttendanceInfo {
+    pub booking_id: Uuid,
+    pub course_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub sessions: Vec<Option<i32>>,
+    pub statuses: Vec<Option<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ParticipantAttendance {
+    pub booking_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    // One entry per course session, None if the session has not been marked
+    pub sessions: Vec<Option<String>>,
+    pub attendance_percentage: f64,
+    pub below_threshold: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CourseAttendance {
+    pub course_id: Uuid,
+    pub sessions: i32,
+    pub sessions_held: i32,
+    pub threshold: f64,
+    pub participants: Vec<ParticipantAttendance>,
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
+pub struct CertificateInfo {
+    pub code: String,
+    pub booking_id: Uuid,
+    pub course_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub course_name: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub sessions: i32,
+    pub issuer: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+// What anyone checking a certificate is shown, without the ids behind it
+#[derive(Serialize, ToSchema)]
+pub struct VerifiedCertificate {
+    pub first_name: String,
+    pub last_name: String,
+    pub course_name: String,
+    pub issued_at: DateTime<Utc>,
+    pub issuer: String,
+}
+
+impl From<CertificateInfo> for VerifiedCertificate {
+    fn from(certificate: CertificateInfo) -> VerifiedCertificate {
+        return VerifiedCertificate {
+            first_name: certificate.first_name,
+            last_name: certificate.last_name,
+            course_name: certificate.course_name,
+            issued_at: certificate.issued_at,
+            issuer: certificate.issuer,
+        };
+    }
+}
+
+// What a booking is answered with, the same whether or not the participant
+// had booked the course before
+#[derive(Serialize, ToSchema)]
+pub struct BookingReceipt {
+    pub course_id: Uuid,
+}
+
+// Where an uploaded course image can be fetched, in each size it is kept in
+#[derive(Serialize, ToSchema)]
+pub struct CourseImage {
+    pub original: String,
+    pub large: String,
+    pub thumbnail: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostnummerInfo {
+    #[schema(value_type = String, example = "113 30")]
+    pub postnummer: Postnummer,
+    pub city: String,
+    pub kommun_code: i32,
+}
+
+// What was answered to a request sent with an Idempotency-Key, no status yet
+// while the request is still being handled
+#[derive(sqlx::FromRow)]
+pub struct IdempotentResponse {
+    pub request_hash: String,
+    pub status: Option<i32>,
+    pub body: Option<String>,
+    pub location: Option<String>,
+}
+
+// What /healthz and /readyz answer with
+#[derive(Serialize)]
+pub struct HealthStatus {
+    pub status: &'static str,
+    pub components: Vec<ComponentStatus>,
+}
+
+#[derive(Serialize)]
+pub struct ComponentStatus {
+    pub name: &'static str,
+    pub status: &'static str,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
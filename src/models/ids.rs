@@ -0,0 +1,75 @@
+use std::{fmt, ops::Deref};
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use sqlx::{
+    encode::IsNull,
+    postgres::{PgArgumentBuffer, PgTypeInfo},
+    Encode, Postgres, Type,
+};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Declares a UUID newtype for one kind of id. It (de)serializes as a plain
+// UUID string, binds as a uuid in queries and derefs to the inner Uuid, so it
+// can be handed straight to the query functions. A malformed id is rejected
+// with an error naming the kind of id, e.g. "Could not parse course_id as a UUID!".
+macro_rules! id_type {
+    ($name:ident, $field:literal) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+        #[serde(transparent)]
+        #[schema(value_type = Uuid)]
+        pub struct $name(pub Uuid);
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+
+                return match Uuid::try_parse(&value) {
+                    Ok(id) => Ok($name(id)),
+                    Err(_) => Err(de::Error::custom(concat!(
+                        "Could not parse ",
+                        $field,
+                        " as a UUID!"
+                    ))),
+                };
+            }
+        }
+
+        impl Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                return <Uuid as Type<Postgres>>::type_info();
+            }
+        }
+
+        impl Encode<'_, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+                return <Uuid as Encode<Postgres>>::encode_by_ref(&self.0, buf);
+            }
+        }
+
+        impl Deref for $name {
+            type Target = Uuid;
+
+            fn deref(&self) -> &Uuid {
+                return &self.0;
+            }
+        }
+
+        impl From<$name> for Uuid {
+            fn from(id: $name) -> Uuid {
+                return id.0;
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                return self.0.fmt(f);
+            }
+        }
+    };
+}
+
+id_type!(CourseId, "course_id");
+id_type!(LocationId, "location_id");
+id_type!(CategoryId, "category_id");
+id_type!(BookingId, "booking_id");
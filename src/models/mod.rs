@@ -1,2 +1,3 @@
 pub mod db;
-pub mod api;
\ No newline at end of file
+pub mod api;
+pub mod ids;
\ No newline at end of file
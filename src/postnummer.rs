@@ -0,0 +1,115 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+// Postal areas keyed by postnummer prefix. A prefix is either the full five
+// digits or the first three, which is enough to tell the postort in most cases.
+const POSTNUMMER_AREAS: &str = include_str!("../data/postnummer.csv");
+
+// A Swedish postnummer, kept as its five digits and shown as "NNN NN"
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "RawPostnummer")]
+pub struct Postnummer(String);
+
+// Clients send postnummer both as text ("113 30") and as a plain number (11330)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPostnummer {
+    Text(String),
+    Number(i64),
+}
+
+impl Postnummer {
+    pub fn digits(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl FromStr for Postnummer {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let digits: String = input.trim().chars().filter(|c| *c != ' ').collect();
+
+        if digits.len() != 5 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!(
+                "\"{}\" is not a valid postnummer, expected five digits like \"113 30\"",
+                input
+            ));
+        }
+
+        return Ok(Postnummer(digits));
+    }
+}
+
+impl TryFrom<RawPostnummer> for Postnummer {
+    type Error = String;
+
+    fn try_from(raw: RawPostnummer) -> Result<Self, Self::Error> {
+        return match raw {
+            RawPostnummer::Text(text) => text.parse(),
+            RawPostnummer::Number(number) => number.to_string().parse(),
+        };
+    }
+}
+
+impl fmt::Display for Postnummer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} {}", &self.0[0..3], &self.0[3..5]);
+    }
+}
+
+impl Serialize for Postnummer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.collect_str(self);
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct PostalArea {
+    pub city: String,
+    pub kommun_code: i32,
+}
+
+// Anything that can tell which postort a postnummer belongs to. The bundled
+// dataset is used by default, an external address service can be swapped in.
+pub trait PostnummerLookup: Send + Sync {
+    fn lookup(&self, postnummer: &Postnummer) -> Option<PostalArea>;
+}
+
+pub struct DatasetLookup {
+    areas: HashMap<String, PostalArea>,
+}
+
+impl DatasetLookup {
+    pub fn bundled() -> Self {
+        let mut areas = HashMap::new();
+
+        for line in POSTNUMMER_AREAS.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            if let [prefix, city, kommun_code] = fields[..] {
+                areas.insert(
+                    prefix.to_string(),
+                    PostalArea {
+                        city: city.to_string(),
+                        kommun_code: kommun_code.parse().expect("Invalid kommun code in dataset"),
+                    },
+                );
+            }
+        }
+
+        return DatasetLookup { areas };
+    }
+}
+
+impl PostnummerLookup for DatasetLookup {
+    fn lookup(&self, postnummer: &Postnummer) -> Option<PostalArea> {
+        let digits = postnummer.digits();
+
+        return self
+            .areas
+            .get(digits)
+            .or_else(|| self.areas.get(&digits[0..3]))
+            .cloned();
+    }
+}
@@ -1,75 +1,119 @@
+// Every query is checked against the schema when compiling. With DATABASE_URL set
+// the macros ask that database, otherwise they read sqlx-data.json. After changing
+// a query or db_query.sql, run `cargo sqlx prepare` against a database built from
+// db_query.sql to regenerate it.
+
 use crate::{
+    helpers::generate_verification_code,
     models::{
         api::{
             CreateBookingRequest, CreateCategoryRequest, CreateCityRequest, CreateCourseRequest,
-            CreateDistrictRequest, CreateSubcategoryRequest,
+            CreateDistrictRequest, CreateSubcategoryRequest, MarkAttendanceRequest,
         },
         db::{
-            Category, CategorySubcategories, Course, CourseBookingInfo, DistrictCities, Location,
+            Category, CategoryTreeNode, CertificateInfo, Course, CourseAttendanceInfo,
+            CourseBookingInfo, DistrictCities, IdempotentResponse, Location,
         },
     },
-    AppState,
+    repos::violates,
 };
 use ::chrono::{DateTime, FixedOffset};
-use actix_web::web::{Data, Json};
-use sqlx::{self, types::chrono::Utc};
+use sqlx::{self, types::chrono::Utc, PgPool};
 use uuid::Uuid;
 
 pub async fn query_get_course_booking_info(
-    state: &Data<AppState>,
+    db: &PgPool,
     course_id: &Uuid,
 ) -> Result<CourseBookingInfo, sqlx::Error> {
-    let result = sqlx::query_as::<_, CourseBookingInfo>(
-        "SELECT * FROM db.course_booking_info WHERE course_id = $1",
+    let result = sqlx::query_as!(
+        CourseBookingInfo,
+        r#"SELECT course_id AS "course_id!", max_seats AS "max_seats!", booking_count AS "booking_count!", personal_numbers AS "personal_numbers!: Vec<Option<i64>>" FROM db.course_booking_info WHERE course_id = $1"#,
+        course_id,
     )
-    .bind(&course_id)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
 pub async fn query_get_districts_cities_tree(
-    state: &Data<AppState>,
+    db: &PgPool,
 ) -> Result<Vec<DistrictCities>, sqlx::Error> {
-    let districts_cities = sqlx::query_as("SELECT * FROM db.district_cities")
-        .fetch_all(&state.db)
-        .await;
+    let result = sqlx::query_as!(
+        DistrictCities,
+        r#"SELECT district_id AS "district_id!", district_name AS "district_name!", district_slug AS "district_slug!", sort_order AS "sort_order!", cities_id AS "cities_id!: Vec<Option<Uuid>>", cities_name AS "cities_name!: Vec<Option<String>>", cities_slug AS "cities_slug!: Vec<Option<String>>" FROM db.district_cities ORDER BY sort_order, district_name COLLATE db.swedish"#,
+    )
+    .fetch_all(db)
+    .await;
 
-    return districts_cities;
+    return result;
 }
 
-pub async fn query_get_categories_subcategories_tree(
-    state: &Data<AppState>,
-) -> Result<Vec<CategorySubcategories>, sqlx::Error> {
-    let category_subcategories = sqlx::query_as("SELECT * FROM db.category_subcategories")
-        .fetch_all(&state.db)
-        .await;
+pub async fn query_get_category_tree(db: &PgPool) -> Result<Vec<CategoryTreeNode>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        CategoryTreeNode,
+        r#"SELECT id AS "id!", category_name AS "category_name!", slug AS "slug!", parent_id, sort_order AS "sort_order!", depth AS "depth!", path AS "path!" FROM db.category_tree ORDER BY depth, sort_order, category_name COLLATE db.swedish"#,
+    )
+    .fetch_all(db)
+    .await;
+
+    return result;
+}
+
+// Returns the chain of categories from the root down to and including `id`
+pub async fn query_get_category_path(db: &PgPool, id: &Uuid) -> Result<Vec<Category>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT cat.id, cat.category_name AS "category_name!", cat.parent_id, cat.sort_order, cat.slug FROM db.category_tree t INNER JOIN db.categories cat ON cat.id = ANY(t.path) WHERE t.id = $1 ORDER BY array_position(t.path, cat.id)"#,
+        id,
+    )
+    .fetch_all(db)
+    .await;
+
+    return result;
+}
+
+// Returns `id` itself together with every category below it
+pub async fn query_get_category_descendant_ids(
+    db: &PgPool,
+    id: &Uuid,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let result = sqlx::query_scalar!(
+        r#"SELECT descendant_id AS "descendant_id!" FROM db.category_descendants WHERE ancestor_id = $1"#,
+        id,
+    )
+    .fetch_all(db)
+    .await;
 
-    return category_subcategories;
+    return result;
 }
 
 pub async fn query_book_course(
-    state: &Data<AppState>,
+    db: &PgPool,
     user_id: &Uuid,
-    booking_details: &Json<CreateBookingRequest>,
+    city: &str,
+    kommun: &Location,
+    booking_details: &CreateBookingRequest,
 ) -> Result<(), sqlx::Error> {
-    let mut tx = state.db.begin().await?;
-
-    let result_create_user = sqlx::query("INSERT INTO db.user (id, personal_number, first_name, last_name, address, co, zipcode, city, kommun, email, mobile) VALUES ($1, $2, $3, $4, $5, NULL, $6, $7, $8, $9, $10)")
-            .bind(&user_id)
-            .bind(&booking_details.personal_number)
-            .bind(&booking_details.first_name)
-            .bind(&booking_details.last_name)
-            .bind(&booking_details.address)
-            .bind(&booking_details.zipcode)
-            .bind(&booking_details.city)
-            .bind(&booking_details.kommun)
-            .bind(&booking_details.email)
-            .bind(&booking_details.mobile)
-            .execute(&mut tx)
-            .await?
-            .rows_affected();
+    let mut tx = db.begin().await?;
+
+    let result_create_user = sqlx::query!(
+        "INSERT INTO db.user (id, personal_number, first_name, last_name, address, co, zipcode, city, kommun, kommun_id, email, mobile) VALUES ($1, $2, $3, $4, $5, NULL, $6, $7, $8, $9, $10, $11)",
+        user_id,
+        booking_details.personal_number,
+        booking_details.first_name,
+        booking_details.last_name,
+        booking_details.address,
+        booking_details.zipcode.digits(),
+        city,
+        kommun.name,
+        kommun.id,
+        booking_details.email,
+        booking_details.mobile,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
 
     if result_create_user == 0 {
         return tx.rollback().await;
@@ -78,15 +122,17 @@ pub async fn query_book_course(
     let booking_id = Uuid::new_v4();
     let booked_at = Utc::now();
 
-    let result_create_booking = sqlx::query("INSERT INTO db.course_bookings (id, course_id, user_id, personal_number, booked_at, paid) VALUES ($1, $2, $3, $4, $5, False)")
-            .bind(&booking_id)
-            .bind(&booking_details.course_id)
-            .bind(&user_id)
-            .bind(&booking_details.personal_number)
-            .bind(&booked_at)
-            .execute(&mut tx)
-            .await?
-            .rows_affected();
+    let result_create_booking = sqlx::query!(
+        "INSERT INTO db.course_bookings (id, course_id, user_id, personal_number, booked_at, paid) VALUES ($1, $2, $3, $4, $5, False)",
+        booking_id,
+        *booking_details.course_id,
+        user_id,
+        booking_details.personal_number,
+        booked_at,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
 
     if result_create_booking == 0 {
         return tx.rollback().await;
@@ -96,31 +142,33 @@ pub async fn query_book_course(
 }
 
 pub async fn query_add_course(
-    state: &Data<AppState>,
+    db: &PgPool,
     id: &Uuid,
     start_date: &DateTime<FixedOffset>,
     end_date: &DateTime<FixedOffset>,
-    course_details: &Json<CreateCourseRequest>,
+    course_details: &CreateCourseRequest,
 ) -> Result<(), sqlx::Error> {
-    let mut tx = state.db.begin().await?;
-
-    let result_create_course = sqlx::query("INSERT INTO db.courses (id, course_name, course_description, start_date, end_date, csn_entitled, max_seats, image, days, hours, price, sessions, visible) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13 )")
-            .bind(&id)
-            .bind(&course_details.course_name.to_string())
-            .bind(&course_details.course_description.to_string())
-            .bind(&start_date)
-            .bind(&end_date)
-            .bind(&course_details.csn_entitled)
-            .bind(&course_details.max_seats)
-            .bind(&course_details.image.to_string())
-            .bind(&course_details.days.to_string())
-            .bind(&course_details.hours.to_string())
-            .bind(&course_details.price)
-            .bind(&course_details.sessions)
-            .bind(&course_details.visible)
-            .execute(&mut tx)
-            .await?
-            .rows_affected();
+    let mut tx = db.begin().await?;
+
+    let result_create_course = sqlx::query!(
+        "INSERT INTO db.courses (id, course_name, course_description, start_date, end_date, csn_entitled, max_seats, image, days, hours, price, sessions, visible) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13 )",
+        id,
+        course_details.course_name,
+        course_details.course_description,
+        start_date,
+        end_date,
+        course_details.csn_entitled,
+        course_details.max_seats,
+        course_details.image,
+        course_details.days,
+        course_details.hours,
+        course_details.price,
+        course_details.sessions,
+        course_details.visible,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
 
     if result_create_course == 0 {
         return tx.rollback().await;
@@ -129,11 +177,11 @@ pub async fn query_add_course(
     if !&course_details.city_ids.is_empty() {
         let mut cities_added: u64 = 0;
         for city_id in &course_details.city_ids {
-            let result = sqlx::query(
+            let result = sqlx::query!(
                 "INSERT INTO db.course_location (course_id, location_id) VALUES ($1, $2)",
+                id,
+                **city_id,
             )
-            .bind(id)
-            .bind(city_id)
             .execute(&mut tx)
             .await?
             .rows_affected();
@@ -149,11 +197,11 @@ pub async fn query_add_course(
         let mut subcategories_added: u64 = 0;
 
         for subcategory_id in &course_details.subcategory_ids {
-            let result = sqlx::query(
+            let result = sqlx::query!(
                 "INSERT INTO db.course_categories (course_id, category_id) VALUES ($1, $2)",
+                id,
+                **subcategory_id,
             )
-            .bind(id)
-            .bind(subcategory_id)
             .execute(&mut tx)
             .await?
             .rows_affected();
@@ -171,251 +219,750 @@ pub async fn query_add_course(
 }
 
 pub async fn query_get_cities_by_district(
-    state: &Data<AppState>,
+    db: &PgPool,
     parent_id: &Uuid,
 ) -> Result<Vec<Location>, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>("SELECT * FROM db.locations WHERE parent_id = $1")
-        .bind(&parent_id)
-        .fetch_all(&state.db)
-        .await;
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE parent_id = $1 ORDER BY sort_order, name COLLATE db.swedish"#,
+        parent_id,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
 pub async fn query_get_city_by_name(
-    state: &Data<AppState>,
+    db: &PgPool,
     parent_id: &Uuid,
-    name: &String,
+    name: &str,
 ) -> Result<Location, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>(
-        "SELECT * FROM db.locations WHERE name = $1 AND parent_id = $2",
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE name = $1 AND parent_id = $2"#,
+        name,
+        parent_id,
     )
-    .bind(&name)
-    .bind(&parent_id)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
 pub async fn query_get_subcategory_by_name(
-    state: &Data<AppState>,
+    db: &PgPool,
     parent_id: &Uuid,
-    name: &String,
+    name: &str,
 ) -> Result<Category, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>(
-        "SELECT * FROM db.categories WHERE category_name = $1 AND parent_id = $2",
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE category_name = $1 AND parent_id = $2"#,
+        name,
+        parent_id,
     )
-    .bind(&name)
-    .bind(&parent_id)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
-pub async fn query_get_category_by_name(
-    state: &Data<AppState>,
-    name: &String,
-) -> Result<Category, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>(
-        "SELECT * FROM db.categories WHERE category_name = $1 AND parent_id IS NULL",
+pub async fn query_get_category_by_name(db: &PgPool, name: &str) -> Result<Category, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE category_name = $1 AND parent_id IS NULL"#,
+        name,
     )
-    .bind(name)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
 pub async fn query_get_category_by_id(
-    state: &Data<AppState>,
+    db: &PgPool,
     category_id: &Uuid,
 ) -> Result<Category, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>(
-        "SELECT * FROM db.categories WHERE id = $1 AND parent_id IS NULL",
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE id = $1"#,
+        category_id,
     )
-    .bind(category_id)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
-pub async fn query_get_categories_all(
-    state: &Data<AppState>,
-) -> Result<Vec<Category>, sqlx::Error> {
-    let result =
-        sqlx::query_as::<_, Category>("SELECT * FROM db.categories WHERE parent_id IS NULL")
-            .fetch_all(&state.db)
-            .await;
+pub async fn query_get_categories_all(db: &PgPool) -> Result<Vec<Category>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE parent_id IS NULL ORDER BY sort_order, category_name COLLATE db.swedish"#,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
 pub async fn query_get_subcategories_by_categoryid(
-    state: &Data<AppState>,
+    db: &PgPool,
     category_id: &Uuid,
 ) -> Result<Vec<Category>, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>("SELECT * FROM db.categories WHERE parent_id = $1")
-        .bind(category_id)
-        .fetch_all(&state.db)
-        .await;
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE parent_id = $1 ORDER BY sort_order, category_name COLLATE db.swedish"#,
+        category_id,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
 pub async fn query_create_category(
-    state: Data<AppState>,
+    db: &PgPool,
     id: &Uuid,
-    body: &Json<CreateCategoryRequest>,
+    slug: &str,
+    body: &CreateCategoryRequest,
 ) -> Result<Category, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>(
-        "INSERT INTO db.categories (id, category_name, parent_id) VALUES ($1, $2, NULL) RETURNING id, category_name, parent_id",
+    let result = sqlx::query_as!(
+        Category,
+        r#"INSERT INTO db.categories (id, category_name, parent_id, sort_order, slug) VALUES ($1, $2, NULL, $3, $4) RETURNING id, category_name AS "category_name!", parent_id, sort_order, slug"#,
+        id,
+        body.category_name,
+        body.sort_order,
+        slug,
     )
-    .bind(id)
-    .bind(body.category_name.to_string())
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
     return result;
 }
 
 pub async fn query_create_subcategory(
-    state: Data<AppState>,
+    db: &PgPool,
     id: &Uuid,
     parent_id: &Uuid,
-    body: &Json<CreateSubcategoryRequest>,
+    slug: &str,
+    body: &CreateSubcategoryRequest,
 ) -> Result<Category, sqlx::Error> {
-    let result = sqlx::query_as::<_, Category>(
-        "INSERT INTO db.categories (id, category_name, parent_id) VALUES ($1, $2, $3) RETURNING id, category_name, parent_id"
+    let result = sqlx::query_as!(
+        Category,
+        r#"INSERT INTO db.categories (id, category_name, parent_id, sort_order, slug) VALUES ($1, $2, $3, $4, $5) RETURNING id, category_name AS "category_name!", parent_id, sort_order, slug"#,
+        id,
+        body.category_name,
+        parent_id,
+        body.sort_order,
+        slug,
     )
-        .bind(id)
-        .bind(body.category_name.to_string())
-        .bind(parent_id)
-        .fetch_one(&state.db)
-        .await;
+    .fetch_one(db)
+    .await;
     return result;
 }
 
-pub async fn query_courses(state: Data<AppState>, id: &Uuid) -> Result<Vec<Course>, sqlx::Error> {
-    let result = sqlx::query_as::<_, Course>("SELECT * FROM db.courses")
-        .bind(id)
-        .fetch_all(&state.db)
-        .await;
+pub async fn query_set_course_image(
+    db: &PgPool,
+    id: &Uuid,
+    image: &str,
+) -> Result<(), sqlx::Error> {
+    let result = sqlx::query!("UPDATE db.courses SET image = $2 WHERE id = $1", id, image)
+        .execute(db)
+        .await?
+        .rows_affected();
 
-    return result;
+    if result == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    return Ok(());
 }
 
-pub async fn query_get_course_by_id(
-    state: &Data<AppState>,
-    id: &Uuid,
-) -> Result<Course, sqlx::Error> {
-    let result = sqlx::query_as::<_, Course>("SELECT * FROM db.courses WHERE id = $1")
-        .bind(id)
-        .fetch_one(&state.db)
-        .await;
+pub async fn query_get_course_by_id(db: &PgPool, id: &Uuid) -> Result<Course, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Course,
+        r#"SELECT id AS "id!", course_name AS "course_name!", course_description AS "course_description!", start_date AS "start_date!", end_date AS "end_date!", csn_entitled AS "csn_entitled!", max_seats AS "max_seats!", image AS "image!", days AS "days!", hours AS "hours!", price AS "price!", sessions AS "sessions!", visible AS "visible!", city_names AS "city_names!: Vec<Option<String>>", subcategory_names AS "subcategory_names!: Vec<Option<String>>" FROM db.full_course_info WHERE id = $1"#,
+        id,
+    )
+    .fetch_one(db)
+    .await;
 
     return result;
 }
 
+// Courses linked to the category or any category below it
 pub async fn query_get_courses_by_category(
-    state: &Data<AppState>,
-    category_name: String,
+    db: &PgPool,
+    category_id: &Uuid,
 ) -> Result<Vec<Course>, sqlx::Error> {
-    let result = sqlx::query_as::<_, Course>("SELECT * FROM db.courses")
-        .fetch_all(&state.db)
-        .await;
+    let result = sqlx::query_as!(
+        Course,
+        r#"SELECT id AS "id!", course_name AS "course_name!", course_description AS "course_description!", start_date AS "start_date!", end_date AS "end_date!", csn_entitled AS "csn_entitled!", max_seats AS "max_seats!", image AS "image!", days AS "days!", hours AS "hours!", price AS "price!", sessions AS "sessions!", visible AS "visible!", city_names AS "city_names!: Vec<Option<String>>", subcategory_names AS "subcategory_names!: Vec<Option<String>>" FROM db.full_course_info WHERE id IN (SELECT cc.course_id FROM db.course_categories cc INNER JOIN db.category_descendants d ON cc.category_id = d.descendant_id WHERE d.ancestor_id = $1)"#,
+        category_id,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
-pub async fn query_get_course_by_name(
-    state: &Data<AppState>,
-    name: String,
-) -> Result<Course, sqlx::Error> {
-    let result = sqlx::query_as::<_, Course>("SELECT * FROM db.courses WHERE course_name = $1")
-        .bind(name)
-        .fetch_one(&state.db)
-        .await;
+pub async fn query_get_course_by_name(db: &PgPool, name: &str) -> Result<Course, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Course,
+        r#"SELECT id AS "id!", course_name AS "course_name!", course_description AS "course_description!", start_date AS "start_date!", end_date AS "end_date!", csn_entitled AS "csn_entitled!", max_seats AS "max_seats!", image AS "image!", days AS "days!", hours AS "hours!", price AS "price!", sessions AS "sessions!", visible AS "visible!", city_names AS "city_names!: Vec<Option<String>>", subcategory_names AS "subcategory_names!: Vec<Option<String>>" FROM db.full_course_info WHERE course_name = $1"#,
+        name,
+    )
+    .fetch_one(db)
+    .await;
 
     return result;
 }
 
-pub async fn query_get_all_courses(state: &Data<AppState>) -> Result<Vec<Course>, sqlx::Error> {
-    let result = sqlx::query_as::<_, Course>("SELECT * FROM db.full_course_info")
-        .fetch_all(&state.db)
-        .await;
+pub async fn query_get_all_courses(db: &PgPool) -> Result<Vec<Course>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Course,
+        r#"SELECT id AS "id!", course_name AS "course_name!", course_description AS "course_description!", start_date AS "start_date!", end_date AS "end_date!", csn_entitled AS "csn_entitled!", max_seats AS "max_seats!", image AS "image!", days AS "days!", hours AS "hours!", price AS "price!", sessions AS "sessions!", visible AS "visible!", city_names AS "city_names!: Vec<Option<String>>", subcategory_names AS "subcategory_names!: Vec<Option<String>>" FROM db.full_course_info"#,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
-pub async fn query_get_location_by_city(
-    state: &Data<AppState>,
-    city: String,
+pub async fn query_get_district_by_id(db: &PgPool, id: &Uuid) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE id = $1 AND parent_id IS NULL"#,
+        id,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_districts(db: &PgPool) -> Result<Vec<Location>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE parent_id IS NULL ORDER BY sort_order, name COLLATE db.swedish"#,
+    )
+    .fetch_all(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_create_district(
+    db: &PgPool,
+    id: Uuid,
+    slug: &str,
+    location_details: &CreateDistrictRequest,
 ) -> Result<Location, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>("SELECT * FROM db.locations WHERE city = $1")
-        .bind(city)
-        .fetch_one(&state.db)
-        .await;
+    let result = sqlx::query_as!(
+        Location,
+        r#"INSERT INTO db.locations (id, name, parent_id, code, sort_order, slug) VALUES ($1, $2, NULL, $3, $4, $5) RETURNING id, name AS "name!", parent_id, code AS "code!", sort_order, slug"#,
+        id,
+        location_details.name,
+        location_details.code,
+        location_details.sort_order,
+        slug,
+    )
+    .fetch_one(db)
+    .await;
 
     return result;
 }
 
-pub async fn query_get_district_by_id(
-    state: &Data<AppState>,
+pub async fn query_create_city(
+    db: &PgPool,
     id: &Uuid,
+    parent_id: &Uuid,
+    slug: &str,
+    location_details: &CreateCityRequest,
 ) -> Result<Location, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>(
-        "SELECT * FROM db.locations WHERE id = $1 AND parent_id IS NULL",
+    let result = sqlx::query_as!(
+        Location,
+        r#"INSERT INTO db.locations (id, name, parent_id, code, sort_order, slug) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, name AS "name!", parent_id, code AS "code!", sort_order, slug"#,
+        id,
+        location_details.name,
+        parent_id,
+        location_details.code,
+        location_details.sort_order,
+        slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_course_attendance_info(
+    db: &PgPool,
+    course_id: &Uuid,
+) -> Result<Vec<CourseAttendanceInfo>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        CourseAttendanceInfo,
+        r#"SELECT booking_id AS "booking_id!", course_id AS "course_id!", first_name AS "first_name!", last_name AS "last_name!", sessions AS "sessions!: Vec<Option<i32>>", statuses AS "statuses!: Vec<Option<String>>" FROM db.course_attendance_info WHERE course_id = $1 ORDER BY last_name, first_name"#,
+        course_id,
     )
-    .bind(&id)
-    .fetch_one(&state.db)
+    .fetch_all(db)
     .await;
 
     return result;
 }
 
-pub async fn query_get_districts(state: &Data<AppState>) -> Result<Vec<Location>, sqlx::Error> {
-    let result =
-        sqlx::query_as::<_, Location>("SELECT * FROM db.locations WHERE parent_id IS NULL")
-            .fetch_all(&state.db)
-            .await;
+pub async fn query_mark_attendance(
+    db: &PgPool,
+    course_id: &Uuid,
+    attendance: &MarkAttendanceRequest,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let marked_at = Utc::now();
+
+    for entry in &attendance.entries {
+        // Only bookings belonging to this course can be marked
+        let result = sqlx::query!(
+            "INSERT INTO db.course_attendance (booking_id, session, status, marked_at) SELECT id, $2, $3, $4 FROM db.course_bookings WHERE id = $1 AND course_id = $5 ON CONFLICT (booking_id, session) DO UPDATE SET status = EXCLUDED.status, marked_at = EXCLUDED.marked_at",
+            *entry.booking_id,
+            attendance.session,
+            entry.status.as_str(),
+            marked_at,
+            course_id,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        if result == 0 {
+            tx.rollback().await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+    }
+
+    return tx.commit().await;
+}
+
+pub async fn query_create_certificates(
+    db: &PgPool,
+    booking_ids: &[Uuid],
+    issuer: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let issued_at = Utc::now();
+
+    for booking_id in booking_ids {
+        // Participants who already have a certificate keep their original code
+        sqlx::query!(
+            "INSERT INTO db.certificates (id, booking_id, code, issuer, issued_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (booking_id) DO NOTHING",
+            Uuid::new_v4(),
+            booking_id,
+            generate_verification_code(),
+            issuer,
+            issued_at,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    return tx.commit().await;
+}
+
+pub async fn query_get_certificates_by_course(
+    db: &PgPool,
+    course_id: &Uuid,
+) -> Result<Vec<CertificateInfo>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        CertificateInfo,
+        r#"SELECT code AS "code!", booking_id AS "booking_id!", course_id AS "course_id!", first_name AS "first_name!", last_name AS "last_name!", course_name AS "course_name!", start_date AS "start_date!", end_date AS "end_date!", sessions AS "sessions!", issuer AS "issuer!", issued_at AS "issued_at!" FROM db.certificate_info WHERE course_id = $1 ORDER BY last_name, first_name"#,
+        course_id,
+    )
+    .fetch_all(db)
+    .await;
 
     return result;
 }
 
-pub async fn query_create_district(
-    state: &Data<AppState>,
-    id: Uuid,
-    location_details: &Json<CreateDistrictRequest>,
-) -> Result<Location, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>(
-        "INSERT INTO db.locations (id, name, parent_id, code) VALUES ($1, $2, NULL, $3) RETURNING id, name, parent_id, code",
+pub async fn query_get_certificate_by_code(
+    db: &PgPool,
+    code: &str,
+) -> Result<CertificateInfo, sqlx::Error> {
+    let result = sqlx::query_as!(
+        CertificateInfo,
+        r#"SELECT code AS "code!", booking_id AS "booking_id!", course_id AS "course_id!", first_name AS "first_name!", last_name AS "last_name!", course_name AS "course_name!", start_date AS "start_date!", end_date AS "end_date!", sessions AS "sessions!", issuer AS "issuer!", issued_at AS "issued_at!" FROM db.certificate_info WHERE code = $1"#,
+        code,
     )
-    .bind(id)
-    .bind(location_details.name.to_string())
-    .bind(location_details.code)
-    .fetch_one(&state.db)
+    .fetch_one(db)
     .await;
 
     return result;
 }
 
-pub async fn query_create_city(
-    state: &Data<AppState>,
+// Counts the courses linked to a category or any category below it
+pub async fn query_count_courses_in_category(db: &PgPool, id: &Uuid) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(DISTINCT cc.course_id) AS "count!" FROM db.course_categories cc INNER JOIN db.category_descendants d ON cc.category_id = d.descendant_id WHERE d.ancestor_id = $1"#,
+        id,
+    )
+    .fetch_one(db)
+    .await?;
+
+    return Ok(count);
+}
+
+pub async fn query_update_category(
+    db: &PgPool,
+    category: &Category,
+) -> Result<Category, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Category,
+        r#"UPDATE db.categories SET category_name = $2, parent_id = $3, sort_order = $4, slug = $5 WHERE id = $1 RETURNING id, category_name AS "category_name!", parent_id, sort_order, slug"#,
+        category.id,
+        category.category_name,
+        category.parent_id,
+        category.sort_order,
+        category.slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+// Deletes a category together with everything below it. Course links are moved to
+// `reassign_to` when given, otherwise the foreign keys make the delete fail.
+pub async fn query_delete_category(
+    db: &PgPool,
     id: &Uuid,
+    reassign_to: &Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    if let Some(target_id) = reassign_to {
+        sqlx::query!(
+            "INSERT INTO db.course_categories (course_id, category_id) SELECT cc.course_id, $2 FROM db.course_categories cc INNER JOIN db.category_descendants d ON cc.category_id = d.descendant_id WHERE d.ancestor_id = $1 ON CONFLICT DO NOTHING",
+            id,
+            target_id,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM db.course_categories WHERE category_id IN (SELECT descendant_id FROM db.category_descendants WHERE ancestor_id = $1)",
+            id,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let result_delete_category = sqlx::query!(
+        "DELETE FROM db.categories WHERE id IN (SELECT descendant_id FROM db.category_descendants WHERE ancestor_id = $1)",
+        id,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    if result_delete_category == 0 {
+        tx.rollback().await?;
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    return tx.commit().await;
+}
+
+// Slugs equal to `base` or derived from it with a numeric suffix
+pub async fn query_get_category_slugs_like(
+    db: &PgPool,
+    base: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let result = sqlx::query_scalar!(
+        "SELECT slug FROM db.categories WHERE slug = $1 OR slug LIKE $1 || '-%'",
+        base
+    )
+    .fetch_all(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_location_slugs_like(
+    db: &PgPool,
+    base: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let result = sqlx::query_scalar!(
+        "SELECT slug FROM db.locations WHERE slug = $1 OR slug LIKE $1 || '-%'",
+        base
+    )
+    .fetch_all(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_category_by_slug(db: &PgPool, slug: &str) -> Result<Category, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Category,
+        r#"SELECT id, category_name AS "category_name!", parent_id, sort_order, slug FROM db.categories WHERE slug = $1"#,
+        slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_location_by_slug(db: &PgPool, slug: &str) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE slug = $1"#,
+        slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_location_by_id(db: &PgPool, id: &Uuid) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE id = $1"#,
+        id,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_district_by_name(db: &PgPool, name: &str) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE name = $1 AND parent_id IS NULL"#,
+        name,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+// Counts the courses given at a location, or at any city in it for districts
+pub async fn query_count_courses_in_location(db: &PgPool, id: &Uuid) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(DISTINCT cl.course_id) AS "count!" FROM db.course_location cl INNER JOIN db.locations l ON cl.location_id = l.id WHERE l.id = $1 OR l.parent_id = $1"#,
+        id,
+    )
+    .fetch_one(db)
+    .await?;
+
+    return Ok(count);
+}
+
+pub async fn query_update_location(
+    db: &PgPool,
+    location: &Location,
+) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"UPDATE db.locations SET name = $2, parent_id = $3, code = $4, sort_order = $5, slug = $6 WHERE id = $1 RETURNING id, name AS "name!", parent_id, code AS "code!", sort_order, slug"#,
+        location.id,
+        location.name,
+        location.parent_id,
+        location.code,
+        location.sort_order,
+        location.slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+// Deletes a location, and for districts all of their cities as well
+pub async fn query_delete_location(db: &PgPool, id: &Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!("DELETE FROM db.locations WHERE parent_id = $1", id)
+        .execute(&mut tx)
+        .await?;
+
+    let result_delete_location = sqlx::query!("DELETE FROM db.locations WHERE id = $1", id)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+    if result_delete_location == 0 {
+        tx.rollback().await?;
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    return tx.commit().await;
+}
+
+pub async fn query_get_district_by_code(db: &PgPool, code: &i32) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE code = $1 AND parent_id IS NULL"#,
+        code,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_get_city_by_code(
+    db: &PgPool,
     parent_id: &Uuid,
-    location_details: &Json<CreateCityRequest>,
+    code: &i32,
+) -> Result<Location, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE code = $1 AND parent_id = $2"#,
+        code,
+        parent_id,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+pub async fn query_insert_location(
+    db: &PgPool,
+    id: &Uuid,
+    name: &str,
+    parent_id: &Option<Uuid>,
+    code: &i32,
+    slug: &str,
 ) -> Result<Location, sqlx::Error> {
-    let result = sqlx::query_as::<_, Location>(
-        "INSERT INTO db.locations (id, name, parent_id, code) VALUES ($1, $2, $3, $4) RETURNING id, name, parent_id, code",
+    let result = sqlx::query_as!(
+        Location,
+        r#"INSERT INTO db.locations (id, name, parent_id, code, slug) VALUES ($1, $2, $3, $4, $5) RETURNING id, name AS "name!", parent_id, code AS "code!", sort_order, slug"#,
+        id,
+        name,
+        *parent_id,
+        code,
+        slug,
+    )
+    .fetch_one(db)
+    .await;
+
+    return result;
+}
+
+// Every kommun, i.e. every location that belongs to a län
+pub async fn query_get_kommuner(db: &PgPool) -> Result<Vec<Location>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Location,
+        r#"SELECT id, name AS "name!", parent_id, code AS "code!", sort_order, slug FROM db.locations WHERE parent_id IS NOT NULL ORDER BY name COLLATE db.swedish"#,
     )
-    .bind(&id)
-    .bind(&location_details.name)
-    .bind(&parent_id)
-    .bind(&location_details.code)
-    .fetch_one(&state.db)
+    .fetch_all(db)
     .await;
 
     return result;
 }
+
+// Claims the key for a new request and returns None, or returns what is stored
+// for it. Expired keys and keys of requests started before `stale_before` that
+// never finished are claimed anew.
+pub async fn query_claim_idempotency_key(
+    db: &PgPool,
+    operation: &str,
+    key: &str,
+    request_hash: &str,
+    expired_before: &DateTime<Utc>,
+    stale_before: &DateTime<Utc>,
+) -> Result<Option<IdempotentResponse>, sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM db.idempotency_keys WHERE created_at < $1"#,
+        expired_before,
+    )
+    .execute(db)
+    .await?;
+
+    let claimed = sqlx::query_scalar!(
+        r#"INSERT INTO db.idempotency_keys (operation, key, request_hash, created_at) VALUES ($1, $2, $3, now()) ON CONFLICT (operation, key) DO UPDATE SET request_hash = EXCLUDED.request_hash, created_at = EXCLUDED.created_at WHERE db.idempotency_keys.status IS NULL AND db.idempotency_keys.created_at < $4 RETURNING key"#,
+        operation,
+        key,
+        request_hash,
+        stale_before,
+    )
+    .fetch_optional(db)
+    .await?;
+
+    if claimed.is_some() {
+        return Ok(None);
+    }
+
+    let stored = sqlx::query_as!(
+        IdempotentResponse,
+        r#"SELECT request_hash, status, body, location FROM db.idempotency_keys WHERE operation = $1 AND key = $2"#,
+        operation,
+        key,
+    )
+    .fetch_optional(db)
+    .await?;
+
+    return Ok(stored);
+}
+
+pub async fn query_store_idempotent_response(
+    db: &PgPool,
+    operation: &str,
+    key: &str,
+    status: i32,
+    body: &str,
+    location: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE db.idempotency_keys SET status = $3, body = $4, location = $5 WHERE operation = $1 AND key = $2"#,
+        operation,
+        key,
+        status,
+        body,
+        location,
+    )
+    .execute(db)
+    .await?;
+
+    return Ok(());
+}
+
+pub async fn query_release_idempotency_key(
+    db: &PgPool,
+    operation: &str,
+    key: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM db.idempotency_keys WHERE operation = $1 AND key = $2 AND status IS NULL"#,
+        operation,
+        key,
+    )
+    .execute(db)
+    .await?;
+
+    return Ok(());
+}
+
+// The version db_query.sql leaves in db.schema_version. A database with another
+// version was built from another db_query.sql than the queries were written for.
+pub const SCHEMA_VERSION: i32 = 1;
+
+// SQLSTATE of a query on a table that does not exist
+const UNDEFINED_TABLE: &str = "42P01";
+
+// The version of the schema, None when it was built before schema_version existed
+pub async fn query_get_schema_version(db: &PgPool) -> Result<Option<i32>, sqlx::Error> {
+    let result = sqlx::query_scalar!(r#"SELECT max(version) FROM db.schema_version"#)
+        .fetch_one(db)
+        .await;
+
+    return match result {
+        Ok(version) => Ok(version),
+        Err(err) if violates(&err, UNDEFINED_TABLE) => Ok(None),
+        Err(err) => Err(err),
+    };
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::HttpRequest;
+
+// Keys kept before buckets that have filled up again are forgotten
+const MAX_KEYS: usize = 100_000;
+
+// A token bucket per key: each holds up to `capacity` tokens, every attempt
+// takes one and they come back at `capacity` per `window`
+pub struct TokenBuckets {
+    capacity: f64,
+    window: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBuckets {
+    pub fn new(capacity: u32, window: Duration) -> TokenBuckets {
+        return TokenBuckets {
+            capacity: capacity as f64,
+            window,
+            buckets: Mutex::new(HashMap::new()),
+        };
+    }
+
+    // Takes a token for `key`, or tells how long until there is one
+    pub fn take(&self, key: &str) -> Result<(), Duration> {
+        return self.take_at(key, Instant::now());
+    }
+
+    fn take_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let rate = self.capacity / self.window.as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_KEYS {
+            let capacity = self.capacity;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate));
+        }
+
+        bucket.tokens -= 1.0;
+        return Ok(());
+    }
+}
+
+// How often bookings may be attempted. Read from the environment when running:
+//   BOOKING_LIMIT_PER_IP               attempts per window from one address (default 20)
+//   BOOKING_LIMIT_PER_PERSONAL_NUMBER  attempts per window for one person (default 5)
+//   BOOKING_LIMIT_WINDOW_SECONDS       length of the window (default 3600)
+//   TRUST_PROXY_HEADERS                take the address from Forwarded or
+//                                      X-Forwarded-For, when behind a proxy (default false)
+pub struct BookingLimits {
+    pub per_ip: TokenBuckets,
+    pub per_personal_number: TokenBuckets,
+    pub trust_proxy_headers: bool,
+}
+
+impl Default for BookingLimits {
+    fn default() -> BookingLimits {
+        let window = Duration::from_secs(3600);
+
+        return BookingLimits {
+            per_ip: TokenBuckets::new(20, window),
+            per_personal_number: TokenBuckets::new(5, window),
+            trust_proxy_headers: false,
+        };
+    }
+}
+
+impl BookingLimits {
+    pub fn from_env() -> BookingLimits {
+        let var = |name: &str, default: u64| {
+            return std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default);
+        };
+        let window = Duration::from_secs(var("BOOKING_LIMIT_WINDOW_SECONDS", 3600));
+
+        return BookingLimits {
+            per_ip: TokenBuckets::new(var("BOOKING_LIMIT_PER_IP", 20) as u32, window),
+            per_personal_number: TokenBuckets::new(
+                var("BOOKING_LIMIT_PER_PERSONAL_NUMBER", 5) as u32,
+                window,
+            ),
+            trust_proxy_headers: std::env::var("TRUST_PROXY_HEADERS").as_deref() == Ok("true"),
+        };
+    }
+
+    // The address the request came from, as far as it can be trusted
+    pub fn client_ip(&self, req: &HttpRequest) -> String {
+        if self.trust_proxy_headers {
+            if let Some(ip) = req.connection_info().realip_remote_addr() {
+                return ip.to_string();
+            }
+        }
+
+        return match req.peer_addr() {
+            Some(addr) => addr.ip().to_string(),
+            None => String::from("unknown"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TokenBuckets;
+
+    #[test]
+    fn refills_over_the_window() {
+        let buckets = TokenBuckets::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(buckets.take_at("10.0.0.1", start).is_ok());
+        assert!(buckets.take_at("10.0.0.1", start).is_ok());
+        assert_eq!(
+            buckets.take_at("10.0.0.1", start),
+            Err(Duration::from_secs(30))
+        );
+
+        // Other keys have buckets of their own
+        assert!(buckets.take_at("10.0.0.2", start).is_ok());
+
+        assert!(buckets
+            .take_at("10.0.0.1", start + Duration::from_secs(30))
+            .is_ok());
+        assert!(buckets
+            .take_at("10.0.0.1", start + Duration::from_secs(30))
+            .is_err());
+    }
+}
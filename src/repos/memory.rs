@@ -0,0 +1,1303 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error::Error as StdError,
+    fmt,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use sqlx::error::DatabaseError;
+use uuid::Uuid;
+
+use super::{
+    BookingRepo, CategoryRepo, CourseRepo, HealthRepo, IdempotencyRepo, LocationRepo,
+    FOREIGN_KEY_VIOLATION, UNIQUE_VIOLATION,
+};
+use crate::{
+    blobstore::MemoryStore,
+    botcheck::NoBotCheck,
+    cache::CatalogueCache,
+    helpers::generate_verification_code,
+    metrics::Metrics,
+    models::{
+        api::{
+            CreateBookingRequest, CreateCategoryRequest, CreateCityRequest, CreateCourseRequest,
+            CreateDistrictRequest, CreateSubcategoryRequest, MarkAttendanceRequest,
+        },
+        db::{
+            Category, CategoryTreeNode, CertificateInfo, Course, CourseAttendanceInfo,
+            CourseBookingInfo, DistrictCities, IdempotentResponse, Location,
+        },
+    },
+    postnummer::DatasetLookup,
+    queries::SCHEMA_VERSION,
+    ratelimit::BookingLimits,
+    security::HttpPolicy,
+    AppState,
+};
+
+// Postgres refuses rows failing a CHECK constraint with this SQLSTATE
+const CHECK_VIOLATION: &str = "23514";
+
+// Stands in for the error Postgres gives when a constraint is violated. Only
+// the SQLSTATE code and constraint are meant to be looked at, not the message.
+#[derive(Debug)]
+struct ConstraintViolation {
+    message: String,
+    code: &'static str,
+    constraint: &'static str,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(&self.message);
+    }
+}
+
+impl StdError for ConstraintViolation {}
+
+impl DatabaseError for ConstraintViolation {
+    fn message(&self) -> &str {
+        return &self.message;
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        return Some(Cow::Borrowed(self.code));
+    }
+
+    fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        return self;
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+        return self;
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+        return self;
+    }
+
+    fn constraint(&self) -> Option<&str> {
+        return Some(self.constraint);
+    }
+}
+
+fn violation(code: &'static str, constraint: &'static str) -> sqlx::Error {
+    return sqlx::Error::Database(Box::new(ConstraintViolation {
+        message: format!("violates constraint \"{}\"", constraint),
+        code,
+        constraint,
+    }));
+}
+
+fn unique_violation(constraint: &'static str) -> sqlx::Error {
+    return violation(UNIQUE_VIOLATION, constraint);
+}
+
+fn foreign_key_violation(constraint: &'static str) -> sqlx::Error {
+    return violation(FOREIGN_KEY_VIOLATION, constraint);
+}
+
+fn check_violation(constraint: &'static str) -> sqlx::Error {
+    return violation(CHECK_VIOLATION, constraint);
+}
+
+fn found<T>(row: Option<T>) -> Result<T, sqlx::Error> {
+    return row.ok_or(sqlx::Error::RowNotFound);
+}
+
+// Sorts the way the Swedish collation does: ignoring case, with å, ä and ö after z
+fn swedish_key(name: &str) -> Vec<u32> {
+    return name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'å' => 'z' as u32 + 1,
+            'ä' | 'æ' => 'z' as u32 + 2,
+            'ö' | 'ø' => 'z' as u32 + 3,
+            'é' | 'è' => 'e' as u32,
+            'ü' => 'y' as u32,
+            c => c as u32,
+        })
+        .collect();
+}
+
+// What array_agg(DISTINCT ...) gives over a LEFT JOIN: sorted, nulls last and
+// a single null when nothing was joined
+fn aggregate<T: Ord>(values: Vec<Option<T>>) -> Vec<Option<T>> {
+    if values.is_empty() {
+        return vec![None];
+    }
+
+    let mut values = values;
+    values.sort_by(|a, b| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    values.dedup();
+
+    return values;
+}
+
+// array_agg over a LEFT JOIN, a single null when nothing was joined
+fn joined<T>(values: Vec<T>) -> Vec<Option<T>> {
+    if values.is_empty() {
+        return vec![None];
+    }
+
+    return values.into_iter().map(Some).collect();
+}
+
+struct CourseRow {
+    id: Uuid,
+    course_name: String,
+    course_description: String,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    csn_entitled: bool,
+    max_seats: i32,
+    image: String,
+    days: String,
+    hours: String,
+    price: i32,
+    sessions: i32,
+    visible: bool,
+}
+
+struct UserRow {
+    id: Uuid,
+    first_name: String,
+    last_name: String,
+    kommun_id: Uuid,
+}
+
+struct BookingRow {
+    id: Uuid,
+    course_id: Uuid,
+    user_id: Uuid,
+    personal_number: i64,
+}
+
+struct AttendanceRow {
+    booking_id: Uuid,
+    session: i32,
+    status: String,
+}
+
+struct IdempotencyRow {
+    request_hash: String,
+    status: Option<i32>,
+    body: Option<String>,
+    location: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+struct CertificateRow {
+    booking_id: Uuid,
+    code: String,
+    issuer: String,
+    issued_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Tables {
+    courses: Vec<CourseRow>,
+    // (course_id, location_id)
+    course_locations: Vec<(Uuid, Uuid)>,
+    // (course_id, category_id)
+    course_categories: Vec<(Uuid, Uuid)>,
+    users: Vec<UserRow>,
+    bookings: Vec<BookingRow>,
+    attendance: Vec<AttendanceRow>,
+    certificates: Vec<CertificateRow>,
+    locations: Vec<Location>,
+    categories: Vec<Category>,
+    // Keyed by (operation, key)
+    idempotency_keys: HashMap<(String, String), IdempotencyRow>,
+}
+
+// Every repository kept in memory, for testing handlers without a database.
+// Clones share the same tables.
+#[derive(Clone, Default)]
+pub struct MemoryRepo {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl MemoryRepo {
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        return self.tables.lock().expect("In-memory tables poisoned");
+    }
+}
+
+impl AppState {
+    // State on top of an empty in-memory store
+    pub fn in_memory() -> AppState {
+        let repo = MemoryRepo::default();
+
+        return AppState {
+            courses: Box::new(repo.clone()),
+            bookings: Box::new(repo.clone()),
+            locations: Box::new(repo.clone()),
+            categories: Box::new(repo.clone()),
+            health: Box::new(repo.clone()),
+            idempotency: Box::new(repo),
+            attendance_threshold: 80.0,
+            certificate_issuer: String::from("Ibn Rushd Studieförbund"),
+            idempotency_ttl: Duration::from_secs(24 * 60 * 60),
+            postnummer_lookup: Box::new(DatasetLookup::bundled()),
+            booking_limits: BookingLimits::default(),
+            bot_check: Box::new(NoBotCheck),
+            catalogue: CatalogueCache::new(Duration::from_secs(60)),
+            blobs: Box::new(MemoryStore::default()),
+            metrics: Metrics::new(None),
+            http: HttpPolicy::default(),
+            shutting_down: AtomicBool::new(false),
+        };
+    }
+}
+
+// db.full_course_info
+impl Tables {
+    fn course(&self, row: &CourseRow) -> Course {
+        let city_names = self
+            .course_locations
+            .iter()
+            .filter(|(course_id, _)| *course_id == row.id)
+            .map(|(_, location_id)| {
+                self.locations
+                    .iter()
+                    .find(|l| l.id == *location_id && l.parent_id.is_some())
+                    .map(|l| l.name.clone())
+            })
+            .collect();
+
+        let subcategory_names = self
+            .course_categories
+            .iter()
+            .filter(|(course_id, _)| *course_id == row.id)
+            .map(|(_, category_id)| {
+                self.categories
+                    .iter()
+                    .find(|c| c.id == *category_id && c.parent_id.is_some())
+                    .map(|c| c.category_name.clone())
+            })
+            .collect();
+
+        return Course {
+            id: row.id,
+            course_name: row.course_name.clone(),
+            course_description: row.course_description.clone(),
+            start_date: row.start_date,
+            end_date: row.end_date,
+            csn_entitled: row.csn_entitled,
+            max_seats: row.max_seats,
+            image: row.image.clone(),
+            days: row.days.clone(),
+            hours: row.hours.clone(),
+            price: row.price,
+            sessions: row.sessions,
+            visible: row.visible,
+            city_names: aggregate(city_names),
+            subcategory_names: aggregate(subcategory_names),
+        };
+    }
+
+    fn find_course(&self, matches: impl Fn(&CourseRow) -> bool) -> Result<Course, sqlx::Error> {
+        let row = found(self.courses.iter().find(|row| matches(row)))?;
+        return Ok(self.course(row));
+    }
+}
+
+// db.category_tree and db.category_descendants
+impl Tables {
+    fn category_tree(&self) -> Vec<CategoryTreeNode> {
+        let mut tree: Vec<CategoryTreeNode> = vec![];
+        let mut level: Vec<CategoryTreeNode> = self
+            .categories
+            .iter()
+            .filter(|c| c.parent_id.is_none())
+            .map(|c| CategoryTreeNode {
+                id: c.id,
+                category_name: c.category_name.clone(),
+                slug: c.slug.clone(),
+                parent_id: None,
+                sort_order: c.sort_order,
+                depth: 0,
+                path: vec![c.id],
+            })
+            .collect();
+
+        while !level.is_empty() {
+            let mut next = vec![];
+
+            for node in &level {
+                for child in self.categories.iter() {
+                    if child.parent_id == Some(node.id) {
+                        let mut path = node.path.clone();
+                        path.push(child.id);
+
+                        next.push(CategoryTreeNode {
+                            id: child.id,
+                            category_name: child.category_name.clone(),
+                            slug: child.slug.clone(),
+                            parent_id: child.parent_id,
+                            sort_order: child.sort_order,
+                            depth: node.depth + 1,
+                            path,
+                        });
+                    }
+                }
+            }
+
+            tree.append(&mut level);
+            level = next;
+        }
+
+        tree.sort_by_key(|node| {
+            (
+                node.depth,
+                node.sort_order,
+                swedish_key(&node.category_name),
+            )
+        });
+
+        return tree;
+    }
+
+    fn descendant_ids(&self, id: &Uuid) -> Vec<Uuid> {
+        if !self.categories.iter().any(|c| c.id == *id) {
+            return vec![];
+        }
+
+        let mut ids = vec![*id];
+        let mut i = 0;
+
+        while i < ids.len() {
+            let parent_id = ids[i];
+            for child in self.categories.iter() {
+                if child.parent_id == Some(parent_id) && !ids.contains(&child.id) {
+                    ids.push(child.id);
+                }
+            }
+            i += 1;
+        }
+
+        return ids;
+    }
+
+    fn courses_in_categories(&self, category_ids: &[Uuid]) -> HashSet<Uuid> {
+        return self
+            .course_categories
+            .iter()
+            .filter(|(_, category_id)| category_ids.contains(category_id))
+            .map(|(course_id, _)| *course_id)
+            .collect();
+    }
+
+    // The checks Postgres makes before a category row is written
+    fn check_category(&self, category: &Category) -> Result<(), sqlx::Error> {
+        if category.parent_id == Some(category.id) {
+            return Err(check_violation("categories_check"));
+        }
+
+        if let Some(parent_id) = category.parent_id {
+            if !self.categories.iter().any(|c| c.id == parent_id) {
+                return Err(foreign_key_violation("categories_parent_id_fkey"));
+            }
+        }
+
+        if self
+            .categories
+            .iter()
+            .any(|c| c.id != category.id && c.slug == category.slug)
+        {
+            return Err(unique_violation("categories_slug_key"));
+        }
+
+        return Ok(());
+    }
+
+    fn insert_category(&mut self, category: Category) -> Result<Category, sqlx::Error> {
+        if self.categories.iter().any(|c| c.id == category.id) {
+            return Err(unique_violation("categories_pkey"));
+        }
+
+        self.check_category(&category)?;
+        self.categories.push(category.clone());
+
+        return Ok(category);
+    }
+}
+
+// db.locations and db.district_cities
+impl Tables {
+    fn sorted_locations(&self, matches: impl Fn(&Location) -> bool) -> Vec<Location> {
+        let mut locations: Vec<Location> = self
+            .locations
+            .iter()
+            .filter(|l| matches(l))
+            .cloned()
+            .collect();
+
+        locations.sort_by_key(|l| (l.sort_order, swedish_key(&l.name)));
+
+        return locations;
+    }
+
+    fn find_location(&self, matches: impl Fn(&Location) -> bool) -> Result<Location, sqlx::Error> {
+        return found(self.locations.iter().find(|l| matches(l)).cloned());
+    }
+
+    // The checks Postgres makes before a location row is written
+    fn check_location(&self, location: &Location) -> Result<(), sqlx::Error> {
+        if let Some(parent_id) = location.parent_id {
+            if !self.locations.iter().any(|l| l.id == parent_id) {
+                return Err(foreign_key_violation("locations_parent_id_fkey"));
+            }
+        }
+
+        let others = || self.locations.iter().filter(|l| l.id != location.id);
+
+        if others().any(|l| l.parent_id == location.parent_id && l.name == location.name) {
+            return Err(match location.parent_id {
+                Some(_) => unique_violation("locations_city_name_key"),
+                None => unique_violation("locations_district_name_key"),
+            });
+        }
+
+        if others().any(|l| l.slug == location.slug) {
+            return Err(unique_violation("locations_slug_key"));
+        }
+
+        return Ok(());
+    }
+
+    fn insert_location(&mut self, location: Location) -> Result<Location, sqlx::Error> {
+        if self.locations.iter().any(|l| l.id == location.id) {
+            return Err(unique_violation("locations_pkey"));
+        }
+
+        self.check_location(&location)?;
+        self.locations.push(location.clone());
+
+        return Ok(location);
+    }
+}
+
+// db.course_attendance_info and db.certificate_info
+impl Tables {
+    fn attendance_info(&self, booking: &BookingRow) -> Option<CourseAttendanceInfo> {
+        let user = self.users.iter().find(|u| u.id == booking.user_id)?;
+
+        let marks: Vec<&AttendanceRow> = self
+            .attendance
+            .iter()
+            .filter(|a| a.booking_id == booking.id)
+            .collect();
+
+        return Some(CourseAttendanceInfo {
+            booking_id: booking.id,
+            course_id: booking.course_id,
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            sessions: joined(marks.iter().map(|a| a.session).collect()),
+            statuses: joined(marks.iter().map(|a| a.status.clone()).collect()),
+        });
+    }
+
+    fn certificate_info(&self, certificate: &CertificateRow) -> Option<CertificateInfo> {
+        let booking = self
+            .bookings
+            .iter()
+            .find(|b| b.id == certificate.booking_id)?;
+        let user = self.users.iter().find(|u| u.id == booking.user_id)?;
+        let course = self.courses.iter().find(|c| c.id == booking.course_id)?;
+
+        return Some(CertificateInfo {
+            code: certificate.code.clone(),
+            booking_id: booking.id,
+            course_id: course.id,
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            course_name: course.course_name.clone(),
+            start_date: course.start_date,
+            end_date: course.end_date,
+            sessions: course.sessions,
+            issuer: certificate.issuer.clone(),
+            issued_at: certificate.issued_at,
+        });
+    }
+}
+
+fn slugs_like<'a>(slugs: impl Iterator<Item = &'a String>, base: &str) -> Vec<String> {
+    let prefix = format!("{}-", base);
+
+    return slugs
+        .filter(|slug| *slug == base || slug.starts_with(&prefix))
+        .cloned()
+        .collect();
+}
+
+#[async_trait]
+impl CourseRepo for MemoryRepo {
+    async fn get_all_courses(&self) -> Result<Vec<Course>, sqlx::Error> {
+        let tables = self.tables();
+        return Ok(tables
+            .courses
+            .iter()
+            .map(|row| tables.course(row))
+            .collect());
+    }
+
+    async fn get_course_by_id(&self, id: &Uuid) -> Result<Course, sqlx::Error> {
+        return self.tables().find_course(|row| row.id == *id);
+    }
+
+    async fn get_course_by_name(&self, name: &str) -> Result<Course, sqlx::Error> {
+        return self.tables().find_course(|row| row.course_name == name);
+    }
+
+    async fn get_courses_by_category(
+        &self,
+        category_id: &Uuid,
+    ) -> Result<Vec<Course>, sqlx::Error> {
+        let tables = self.tables();
+        let course_ids = tables.courses_in_categories(&tables.descendant_ids(category_id));
+
+        return Ok(tables
+            .courses
+            .iter()
+            .filter(|row| course_ids.contains(&row.id))
+            .map(|row| tables.course(row))
+            .collect());
+    }
+
+    async fn add_course(
+        &self,
+        id: &Uuid,
+        start_date: &DateTime<FixedOffset>,
+        end_date: &DateTime<FixedOffset>,
+        course: &CreateCourseRequest,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        if tables
+            .courses
+            .iter()
+            .any(|row| row.course_name == course.course_name)
+        {
+            return Err(unique_violation("courses_course_name_key"));
+        }
+
+        let mut locations: Vec<(Uuid, Uuid)> = vec![];
+        for city_id in &course.city_ids {
+            if !tables.locations.iter().any(|l| l.id == **city_id) {
+                return Err(foreign_key_violation("course_location_location_id_fkey"));
+            }
+            if locations.contains(&(*id, **city_id)) {
+                return Err(unique_violation("course_location_pkey"));
+            }
+            locations.push((*id, **city_id));
+        }
+
+        let mut categories: Vec<(Uuid, Uuid)> = vec![];
+        for subcategory_id in &course.subcategory_ids {
+            if !tables.categories.iter().any(|c| c.id == **subcategory_id) {
+                return Err(foreign_key_violation("course_categories_category_id_fkey"));
+            }
+            if categories.contains(&(*id, **subcategory_id)) {
+                return Err(unique_violation("course_categories_pkey"));
+            }
+            categories.push((*id, **subcategory_id));
+        }
+
+        tables.courses.push(CourseRow {
+            id: *id,
+            course_name: course.course_name.clone(),
+            course_description: course.course_description.clone(),
+            start_date: start_date.with_timezone(&Utc),
+            end_date: end_date.with_timezone(&Utc),
+            csn_entitled: course.csn_entitled,
+            max_seats: course.max_seats,
+            image: course.image.clone(),
+            days: course.days.clone(),
+            hours: course.hours.clone(),
+            price: course.price,
+            sessions: course.sessions,
+            visible: course.visible,
+        });
+        tables.course_locations.append(&mut locations);
+        tables.course_categories.append(&mut categories);
+
+        return Ok(());
+    }
+
+    async fn set_course_image(&self, id: &Uuid, image: &str) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+        let course = found(tables.courses.iter_mut().find(|row| row.id == *id))?;
+        course.image = image.to_string();
+
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl BookingRepo for MemoryRepo {
+    async fn get_course_booking_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<CourseBookingInfo, sqlx::Error> {
+        let tables = self.tables();
+        let course = found(tables.courses.iter().find(|row| row.id == *course_id))?;
+
+        let personal_numbers: Vec<i64> = tables
+            .bookings
+            .iter()
+            .filter(|b| b.course_id == *course_id)
+            .map(|b| b.personal_number)
+            .collect();
+
+        return Ok(CourseBookingInfo {
+            course_id: course.id,
+            max_seats: course.max_seats,
+            booking_count: personal_numbers.len() as i64,
+            personal_numbers: joined(personal_numbers),
+        });
+    }
+
+    async fn book_course(
+        &self,
+        user_id: &Uuid,
+        _city: &str,
+        kommun: &Location,
+        booking: &CreateBookingRequest,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        if !tables.locations.iter().any(|l| l.id == kommun.id) {
+            return Err(foreign_key_violation("user_kommun_id_fkey"));
+        }
+
+        if !tables
+            .courses
+            .iter()
+            .any(|row| row.id == *booking.course_id)
+        {
+            return Err(foreign_key_violation("course_bookings_course_id_fkey"));
+        }
+
+        tables.users.push(UserRow {
+            id: *user_id,
+            first_name: booking.first_name.clone(),
+            last_name: booking.last_name.clone(),
+            kommun_id: kommun.id,
+        });
+        tables.bookings.push(BookingRow {
+            id: Uuid::new_v4(),
+            course_id: *booking.course_id,
+            user_id: *user_id,
+            personal_number: booking.personal_number,
+        });
+
+        return Ok(());
+    }
+
+    async fn get_course_attendance_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CourseAttendanceInfo>, sqlx::Error> {
+        let tables = self.tables();
+
+        let mut infos: Vec<CourseAttendanceInfo> = tables
+            .bookings
+            .iter()
+            .filter(|b| b.course_id == *course_id)
+            .filter_map(|b| tables.attendance_info(b))
+            .collect();
+
+        infos.sort_by(|a, b| (&a.last_name, &a.first_name).cmp(&(&b.last_name, &b.first_name)));
+
+        return Ok(infos);
+    }
+
+    async fn mark_attendance(
+        &self,
+        course_id: &Uuid,
+        attendance: &MarkAttendanceRequest,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        for entry in &attendance.entries {
+            if !tables
+                .bookings
+                .iter()
+                .any(|b| b.id == *entry.booking_id && b.course_id == *course_id)
+            {
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+
+        for entry in &attendance.entries {
+            let status = entry.status.as_str().to_string();
+
+            match tables
+                .attendance
+                .iter_mut()
+                .find(|a| a.booking_id == *entry.booking_id && a.session == attendance.session)
+            {
+                Some(mark) => mark.status = status,
+                None => tables.attendance.push(AttendanceRow {
+                    booking_id: *entry.booking_id,
+                    session: attendance.session,
+                    status,
+                }),
+            }
+        }
+
+        return Ok(());
+    }
+
+    async fn create_certificates(
+        &self,
+        booking_ids: &[Uuid],
+        issuer: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        for booking_id in booking_ids {
+            if !tables.bookings.iter().any(|b| b.id == *booking_id) {
+                return Err(foreign_key_violation("certificates_booking_id_fkey"));
+            }
+        }
+
+        let issued_at = Utc::now();
+
+        for booking_id in booking_ids {
+            if !tables
+                .certificates
+                .iter()
+                .any(|c| c.booking_id == *booking_id)
+            {
+                tables.certificates.push(CertificateRow {
+                    booking_id: *booking_id,
+                    code: generate_verification_code(),
+                    issuer: issuer.to_string(),
+                    issued_at,
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    async fn get_certificates_by_course(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CertificateInfo>, sqlx::Error> {
+        let tables = self.tables();
+
+        let mut certificates: Vec<CertificateInfo> = tables
+            .certificates
+            .iter()
+            .filter_map(|c| tables.certificate_info(c))
+            .filter(|c| c.course_id == *course_id)
+            .collect();
+
+        certificates
+            .sort_by(|a, b| (&a.last_name, &a.first_name).cmp(&(&b.last_name, &b.first_name)));
+
+        return Ok(certificates);
+    }
+
+    async fn get_certificate_by_code(&self, code: &str) -> Result<CertificateInfo, sqlx::Error> {
+        let tables = self.tables();
+        let certificate = found(tables.certificates.iter().find(|c| c.code == code))?;
+
+        return found(tables.certificate_info(certificate));
+    }
+}
+
+#[async_trait]
+impl LocationRepo for MemoryRepo {
+    async fn get_districts_cities_tree(&self) -> Result<Vec<DistrictCities>, sqlx::Error> {
+        let tables = self.tables();
+
+        return Ok(tables
+            .sorted_locations(|l| l.parent_id.is_none())
+            .into_iter()
+            .map(|district| {
+                let cities = tables.sorted_locations(|l| l.parent_id == Some(district.id));
+
+                DistrictCities {
+                    district_id: district.id,
+                    district_name: district.name,
+                    district_slug: district.slug,
+                    sort_order: district.sort_order,
+                    cities_id: joined(cities.iter().map(|c| c.id).collect()),
+                    cities_name: joined(cities.iter().map(|c| c.name.clone()).collect()),
+                    cities_slug: joined(cities.iter().map(|c| c.slug.clone()).collect()),
+                }
+            })
+            .collect());
+    }
+
+    async fn get_districts(&self) -> Result<Vec<Location>, sqlx::Error> {
+        return Ok(self.tables().sorted_locations(|l| l.parent_id.is_none()));
+    }
+
+    async fn get_district_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error> {
+        return self
+            .tables()
+            .find_location(|l| l.id == *id && l.parent_id.is_none());
+    }
+
+    async fn get_district_by_name(&self, name: &str) -> Result<Location, sqlx::Error> {
+        return self
+            .tables()
+            .find_location(|l| l.name == name && l.parent_id.is_none());
+    }
+
+    async fn get_district_by_code(&self, code: &i32) -> Result<Location, sqlx::Error> {
+        return self
+            .tables()
+            .find_location(|l| l.code == *code && l.parent_id.is_none());
+    }
+
+    async fn get_cities_by_district(&self, parent_id: &Uuid) -> Result<Vec<Location>, sqlx::Error> {
+        return Ok(self
+            .tables()
+            .sorted_locations(|l| l.parent_id == Some(*parent_id)));
+    }
+
+    async fn get_city_by_name(
+        &self,
+        parent_id: &Uuid,
+        name: &str,
+    ) -> Result<Location, sqlx::Error> {
+        return self
+            .tables()
+            .find_location(|l| l.name == name && l.parent_id == Some(*parent_id));
+    }
+
+    async fn get_city_by_code(
+        &self,
+        parent_id: &Uuid,
+        code: &i32,
+    ) -> Result<Location, sqlx::Error> {
+        return self
+            .tables()
+            .find_location(|l| l.code == *code && l.parent_id == Some(*parent_id));
+    }
+
+    async fn get_kommuner(&self) -> Result<Vec<Location>, sqlx::Error> {
+        let mut kommuner = self.tables().sorted_locations(|l| l.parent_id.is_some());
+        kommuner.sort_by_key(|l| swedish_key(&l.name));
+
+        return Ok(kommuner);
+    }
+
+    async fn get_location_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error> {
+        return self.tables().find_location(|l| l.id == *id);
+    }
+
+    async fn get_location_by_slug(&self, slug: &str) -> Result<Location, sqlx::Error> {
+        return self.tables().find_location(|l| l.slug == slug);
+    }
+
+    async fn get_location_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error> {
+        return Ok(slugs_like(
+            self.tables().locations.iter().map(|l| &l.slug),
+            base,
+        ));
+    }
+
+    async fn count_courses_in_location(&self, id: &Uuid) -> Result<i64, sqlx::Error> {
+        let tables = self.tables();
+
+        let location_ids: Vec<Uuid> = tables
+            .locations
+            .iter()
+            .filter(|l| l.id == *id || l.parent_id == Some(*id))
+            .map(|l| l.id)
+            .collect();
+
+        let course_ids: HashSet<Uuid> = tables
+            .course_locations
+            .iter()
+            .filter(|(_, location_id)| location_ids.contains(location_id))
+            .map(|(course_id, _)| *course_id)
+            .collect();
+
+        return Ok(course_ids.len() as i64);
+    }
+
+    async fn create_district(
+        &self,
+        id: Uuid,
+        slug: &str,
+        district: &CreateDistrictRequest,
+    ) -> Result<Location, sqlx::Error> {
+        return self.tables().insert_location(Location {
+            id,
+            name: district.name.clone(),
+            parent_id: None,
+            code: district.code,
+            sort_order: district.sort_order,
+            slug: slug.to_string(),
+        });
+    }
+
+    async fn create_city(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        city: &CreateCityRequest,
+    ) -> Result<Location, sqlx::Error> {
+        return self.tables().insert_location(Location {
+            id: *id,
+            name: city.name.clone(),
+            parent_id: Some(*parent_id),
+            code: city.code,
+            sort_order: city.sort_order,
+            slug: slug.to_string(),
+        });
+    }
+
+    async fn insert_location(
+        &self,
+        id: &Uuid,
+        name: &str,
+        parent_id: &Option<Uuid>,
+        code: &i32,
+        slug: &str,
+    ) -> Result<Location, sqlx::Error> {
+        return self.tables().insert_location(Location {
+            id: *id,
+            name: name.to_string(),
+            parent_id: *parent_id,
+            code: *code,
+            sort_order: 0,
+            slug: slug.to_string(),
+        });
+    }
+
+    async fn update_location(&self, location: &Location) -> Result<Location, sqlx::Error> {
+        let mut tables = self.tables();
+
+        found(tables.locations.iter().find(|l| l.id == location.id))?;
+        tables.check_location(location)?;
+
+        for existing in tables.locations.iter_mut() {
+            if existing.id == location.id {
+                *existing = location.clone();
+            }
+        }
+
+        return Ok(location.clone());
+    }
+
+    async fn delete_location(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        found(tables.locations.iter().find(|l| l.id == *id))?;
+
+        let deleted: Vec<Uuid> = tables
+            .locations
+            .iter()
+            .filter(|l| l.id == *id || l.parent_id == Some(*id))
+            .map(|l| l.id)
+            .collect();
+
+        if tables
+            .course_locations
+            .iter()
+            .any(|(_, location_id)| deleted.contains(location_id))
+        {
+            return Err(foreign_key_violation("course_location_location_id_fkey"));
+        }
+
+        if tables.users.iter().any(|u| deleted.contains(&u.kommun_id)) {
+            return Err(foreign_key_violation("user_kommun_id_fkey"));
+        }
+
+        tables.locations.retain(|l| !deleted.contains(&l.id));
+
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for MemoryRepo {
+    async fn get_category_tree(&self) -> Result<Vec<CategoryTreeNode>, sqlx::Error> {
+        return Ok(self.tables().category_tree());
+    }
+
+    async fn get_category_by_id(&self, category_id: &Uuid) -> Result<Category, sqlx::Error> {
+        let tables = self.tables();
+        return found(
+            tables
+                .categories
+                .iter()
+                .find(|c| c.id == *category_id)
+                .cloned(),
+        );
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Category, sqlx::Error> {
+        let tables = self.tables();
+
+        return found(
+            tables
+                .categories
+                .iter()
+                .find(|c| c.category_name == name && c.parent_id.is_none())
+                .cloned(),
+        );
+    }
+
+    async fn get_subcategory_by_name(
+        &self,
+        parent_id: &Uuid,
+        name: &str,
+    ) -> Result<Category, sqlx::Error> {
+        let tables = self.tables();
+
+        return found(
+            tables
+                .categories
+                .iter()
+                .find(|c| c.category_name == name && c.parent_id == Some(*parent_id))
+                .cloned(),
+        );
+    }
+
+    async fn get_subcategories_by_categoryid(
+        &self,
+        category_id: &Uuid,
+    ) -> Result<Vec<Category>, sqlx::Error> {
+        let mut subcategories: Vec<Category> = self
+            .tables()
+            .categories
+            .iter()
+            .filter(|c| c.parent_id == Some(*category_id))
+            .cloned()
+            .collect();
+
+        subcategories.sort_by_key(|c| (c.sort_order, swedish_key(&c.category_name)));
+
+        return Ok(subcategories);
+    }
+
+    async fn get_category_by_slug(&self, slug: &str) -> Result<Category, sqlx::Error> {
+        let tables = self.tables();
+        return found(tables.categories.iter().find(|c| c.slug == slug).cloned());
+    }
+
+    async fn get_category_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error> {
+        return Ok(slugs_like(
+            self.tables().categories.iter().map(|c| &c.slug),
+            base,
+        ));
+    }
+
+    async fn get_category_path(&self, id: &Uuid) -> Result<Vec<Category>, sqlx::Error> {
+        let tables = self.tables();
+
+        let path = match tables
+            .category_tree()
+            .into_iter()
+            .find(|node| node.id == *id)
+        {
+            Some(node) => node.path,
+            None => return Ok(vec![]),
+        };
+
+        return Ok(path
+            .iter()
+            .filter_map(|id| tables.categories.iter().find(|c| c.id == *id).cloned())
+            .collect());
+    }
+
+    async fn get_category_descendant_ids(&self, id: &Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        return Ok(self.tables().descendant_ids(id));
+    }
+
+    async fn count_courses_in_category(&self, id: &Uuid) -> Result<i64, sqlx::Error> {
+        let tables = self.tables();
+        let course_ids = tables.courses_in_categories(&tables.descendant_ids(id));
+
+        return Ok(course_ids.len() as i64);
+    }
+
+    async fn create_category(
+        &self,
+        id: &Uuid,
+        slug: &str,
+        category: &CreateCategoryRequest,
+    ) -> Result<Category, sqlx::Error> {
+        return self.tables().insert_category(Category {
+            id: *id,
+            category_name: category.category_name.to_string(),
+            parent_id: None,
+            sort_order: category.sort_order,
+            slug: slug.to_string(),
+        });
+    }
+
+    async fn create_subcategory(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        subcategory: &CreateSubcategoryRequest,
+    ) -> Result<Category, sqlx::Error> {
+        return self.tables().insert_category(Category {
+            id: *id,
+            category_name: subcategory.category_name.to_string(),
+            parent_id: Some(*parent_id),
+            sort_order: subcategory.sort_order,
+            slug: slug.to_string(),
+        });
+    }
+
+    async fn update_category(&self, category: &Category) -> Result<Category, sqlx::Error> {
+        let mut tables = self.tables();
+
+        found(tables.categories.iter().find(|c| c.id == category.id))?;
+        tables.check_category(category)?;
+
+        for existing in tables.categories.iter_mut() {
+            if existing.id == category.id {
+                *existing = category.clone();
+            }
+        }
+
+        return Ok(category.clone());
+    }
+
+    async fn delete_category(
+        &self,
+        id: &Uuid,
+        reassign_to: &Option<Uuid>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+
+        let deleted = tables.descendant_ids(id);
+        if deleted.is_empty() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let mut links = tables.course_categories.clone();
+
+        if let Some(target_id) = reassign_to {
+            if !tables.categories.iter().any(|c| c.id == *target_id) {
+                return Err(foreign_key_violation("course_categories_category_id_fkey"));
+            }
+
+            for course_id in tables.courses_in_categories(&deleted) {
+                if !links.contains(&(course_id, *target_id)) {
+                    links.push((course_id, *target_id));
+                }
+            }
+
+            links.retain(|(_, category_id)| !deleted.contains(category_id));
+        }
+
+        if links
+            .iter()
+            .any(|(_, category_id)| deleted.contains(category_id))
+        {
+            return Err(foreign_key_violation("course_categories_category_id_fkey"));
+        }
+
+        tables.course_categories = links;
+        tables.categories.retain(|c| !deleted.contains(&c.id));
+
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepo for MemoryRepo {
+    async fn claim_idempotency_key(
+        &self,
+        operation: &str,
+        key: &str,
+        request_hash: &str,
+        expired_before: &DateTime<Utc>,
+        stale_before: &DateTime<Utc>,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error> {
+        let mut tables = self.tables();
+        tables
+            .idempotency_keys
+            .retain(|_, row| row.created_at >= *expired_before);
+
+        let id = (operation.to_string(), key.to_string());
+        if let Some(row) = tables.idempotency_keys.get(&id) {
+            if row.status.is_some() || row.created_at >= *stale_before {
+                return Ok(Some(IdempotentResponse {
+                    request_hash: row.request_hash.clone(),
+                    status: row.status,
+                    body: row.body.clone(),
+                    location: row.location.clone(),
+                }));
+            }
+        }
+
+        tables.idempotency_keys.insert(
+            id,
+            IdempotencyRow {
+                request_hash: request_hash.to_string(),
+                status: None,
+                body: None,
+                location: None,
+                created_at: Utc::now(),
+            },
+        );
+        return Ok(None);
+    }
+
+    async fn store_idempotent_response(
+        &self,
+        operation: &str,
+        key: &str,
+        status: i32,
+        body: &str,
+        location: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let id = (operation.to_string(), key.to_string());
+        if let Some(row) = self.tables().idempotency_keys.get_mut(&id) {
+            row.status = Some(status);
+            row.body = Some(body.to_string());
+            row.location = location.map(str::to_string);
+        }
+
+        return Ok(());
+    }
+
+    async fn release_idempotency_key(&self, operation: &str, key: &str) -> Result<(), sqlx::Error> {
+        let id = (operation.to_string(), key.to_string());
+        let mut tables = self.tables();
+        if tables
+            .idempotency_keys
+            .get(&id)
+            .is_some_and(|row| row.status.is_none())
+        {
+            tables.idempotency_keys.remove(&id);
+        }
+
+        return Ok(());
+    }
+}
+
+// The in-memory store is always there and always has the whole schema
+#[async_trait]
+impl HealthRepo for MemoryRepo {
+    async fn get_schema_version(&self) -> Result<Option<i32>, sqlx::Error> {
+        return Ok(Some(SCHEMA_VERSION));
+    }
+}
@@ -0,0 +1,247 @@
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use uuid::Uuid;
+
+use crate::models::{
+    api::{
+        CreateBookingRequest, CreateCategoryRequest, CreateCityRequest, CreateCourseRequest,
+        CreateDistrictRequest, CreateSubcategoryRequest, MarkAttendanceRequest,
+    },
+    db::{
+        Category, CategoryTreeNode, CertificateInfo, Course, CourseAttendanceInfo,
+        CourseBookingInfo, DistrictCities, IdempotentResponse, Location,
+    },
+};
+
+#[cfg(test)]
+pub mod memory;
+pub mod postgres;
+
+// Storage behind the handlers, split by the part of the data they own. Postgres
+// is used when running, the in-memory store lets handlers be tested without a
+// database. Both fail with the errors sqlx would give, so handlers can tell
+// a missing row from RowNotFound and a duplicate or foreign key violation from
+// its SQLSTATE code (see `violates`), never from the message.
+
+// Courses with the cities and subcategories they are given in
+#[async_trait]
+pub trait CourseRepo: Send + Sync {
+    async fn get_all_courses(&self) -> Result<Vec<Course>, sqlx::Error>;
+    async fn get_course_by_id(&self, id: &Uuid) -> Result<Course, sqlx::Error>;
+    async fn get_course_by_name(&self, name: &str) -> Result<Course, sqlx::Error>;
+
+    // Courses linked to the category or any category below it
+    async fn get_courses_by_category(&self, category_id: &Uuid)
+        -> Result<Vec<Course>, sqlx::Error>;
+
+    // Adds the course together with its cities and subcategories
+    async fn add_course(
+        &self,
+        id: &Uuid,
+        start_date: &DateTime<FixedOffset>,
+        end_date: &DateTime<FixedOffset>,
+        course: &CreateCourseRequest,
+    ) -> Result<(), sqlx::Error>;
+
+    // Points the course at an uploaded image, RowNotFound for unknown courses
+    async fn set_course_image(&self, id: &Uuid, image: &str) -> Result<(), sqlx::Error>;
+}
+
+// Bookings and what is recorded per booking: attendance and certificates
+#[async_trait]
+pub trait BookingRepo: Send + Sync {
+    // Seats and the personal numbers already booked on a course
+    async fn get_course_booking_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<CourseBookingInfo, sqlx::Error>;
+
+    // Adds the participant and their booking
+    async fn book_course(
+        &self,
+        user_id: &Uuid,
+        city: &str,
+        kommun: &Location,
+        booking: &CreateBookingRequest,
+    ) -> Result<(), sqlx::Error>;
+    async fn get_course_attendance_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CourseAttendanceInfo>, sqlx::Error>;
+
+    // Fails with RowNotFound, marking nothing, if any booking is from another course
+    async fn mark_attendance(
+        &self,
+        course_id: &Uuid,
+        attendance: &MarkAttendanceRequest,
+    ) -> Result<(), sqlx::Error>;
+
+    // Bookings that already have a certificate keep it
+    async fn create_certificates(
+        &self,
+        booking_ids: &[Uuid],
+        issuer: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn get_certificates_by_course(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CertificateInfo>, sqlx::Error>;
+    async fn get_certificate_by_code(&self, code: &str) -> Result<CertificateInfo, sqlx::Error>;
+}
+
+// Districts (län) and the cities (kommun) in them
+#[async_trait]
+pub trait LocationRepo: Send + Sync {
+    async fn get_districts_cities_tree(&self) -> Result<Vec<DistrictCities>, sqlx::Error>;
+    async fn get_districts(&self) -> Result<Vec<Location>, sqlx::Error>;
+    async fn get_district_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error>;
+    async fn get_district_by_name(&self, name: &str) -> Result<Location, sqlx::Error>;
+    async fn get_district_by_code(&self, code: &i32) -> Result<Location, sqlx::Error>;
+    async fn get_cities_by_district(&self, parent_id: &Uuid) -> Result<Vec<Location>, sqlx::Error>;
+    async fn get_city_by_name(&self, parent_id: &Uuid, name: &str)
+        -> Result<Location, sqlx::Error>;
+    async fn get_city_by_code(&self, parent_id: &Uuid, code: &i32)
+        -> Result<Location, sqlx::Error>;
+
+    // Every kommun, i.e. every location that belongs to a län
+    async fn get_kommuner(&self) -> Result<Vec<Location>, sqlx::Error>;
+    async fn get_location_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error>;
+    async fn get_location_by_slug(&self, slug: &str) -> Result<Location, sqlx::Error>;
+
+    // Slugs equal to `base` or derived from it with a numeric suffix
+    async fn get_location_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error>;
+
+    // Courses given at the location, or at any city in it for districts
+    async fn count_courses_in_location(&self, id: &Uuid) -> Result<i64, sqlx::Error>;
+    async fn create_district(
+        &self,
+        id: Uuid,
+        slug: &str,
+        district: &CreateDistrictRequest,
+    ) -> Result<Location, sqlx::Error>;
+    async fn create_city(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        city: &CreateCityRequest,
+    ) -> Result<Location, sqlx::Error>;
+    async fn insert_location(
+        &self,
+        id: &Uuid,
+        name: &str,
+        parent_id: &Option<Uuid>,
+        code: &i32,
+        slug: &str,
+    ) -> Result<Location, sqlx::Error>;
+    async fn update_location(&self, location: &Location) -> Result<Location, sqlx::Error>;
+
+    // Deletes a location, and for districts all of their cities as well
+    async fn delete_location(&self, id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+// The category tree
+#[async_trait]
+pub trait CategoryRepo: Send + Sync {
+    // Every category reachable from a top level one, in display order per level
+    async fn get_category_tree(&self) -> Result<Vec<CategoryTreeNode>, sqlx::Error>;
+    async fn get_category_by_id(&self, category_id: &Uuid) -> Result<Category, sqlx::Error>;
+
+    // Top level category with the given name
+    async fn get_category_by_name(&self, name: &str) -> Result<Category, sqlx::Error>;
+    async fn get_subcategory_by_name(
+        &self,
+        parent_id: &Uuid,
+        name: &str,
+    ) -> Result<Category, sqlx::Error>;
+    async fn get_subcategories_by_categoryid(
+        &self,
+        category_id: &Uuid,
+    ) -> Result<Vec<Category>, sqlx::Error>;
+    async fn get_category_by_slug(&self, slug: &str) -> Result<Category, sqlx::Error>;
+
+    // Slugs equal to `base` or derived from it with a numeric suffix
+    async fn get_category_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error>;
+
+    // The chain of categories from the root down to and including `id`
+    async fn get_category_path(&self, id: &Uuid) -> Result<Vec<Category>, sqlx::Error>;
+
+    // `id` itself together with every category below it
+    async fn get_category_descendant_ids(&self, id: &Uuid) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    // Courses linked to the category or any category below it
+    async fn count_courses_in_category(&self, id: &Uuid) -> Result<i64, sqlx::Error>;
+    async fn create_category(
+        &self,
+        id: &Uuid,
+        slug: &str,
+        category: &CreateCategoryRequest,
+    ) -> Result<Category, sqlx::Error>;
+    async fn create_subcategory(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        subcategory: &CreateSubcategoryRequest,
+    ) -> Result<Category, sqlx::Error>;
+    async fn update_category(&self, category: &Category) -> Result<Category, sqlx::Error>;
+
+    // Deletes the category and everything below it, moving their courses to `reassign_to` if given
+    async fn delete_category(
+        &self,
+        id: &Uuid,
+        reassign_to: &Option<Uuid>,
+    ) -> Result<(), sqlx::Error>;
+}
+
+// Responses to requests sent with an Idempotency-Key, kept so retries get the
+// same answer. Keys belong to an operation, such as making a booking.
+#[async_trait]
+pub trait IdempotencyRepo: Send + Sync {
+    // Claims the key for a new request and returns None, or returns what is
+    // stored for it. Keys created before `expired_before`, and keys of requests
+    // started before `stale_before` that never finished, are claimed anew.
+    async fn claim_idempotency_key(
+        &self,
+        operation: &str,
+        key: &str,
+        request_hash: &str,
+        expired_before: &DateTime<Utc>,
+        stale_before: &DateTime<Utc>,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error>;
+
+    async fn store_idempotent_response(
+        &self,
+        operation: &str,
+        key: &str,
+        status: i32,
+        body: &str,
+        location: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    // Lets the key be claimed again, when the request failed without an answer
+    // worth replaying
+    async fn release_idempotency_key(&self, operation: &str, key: &str) -> Result<(), sqlx::Error>;
+}
+
+// Whether the database can be reached and has the schema the queries expect
+#[async_trait]
+pub trait HealthRepo: Send + Sync {
+    // The version db_query.sql left in the database, None if it predates versions
+    async fn get_schema_version(&self) -> Result<Option<i32>, sqlx::Error>;
+}
+
+// SQLSTATE codes of the constraint violations handlers answer with a 4xx
+pub const UNIQUE_VIOLATION: &str = "23505";
+pub const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+// Whether the database refused the query with the given SQLSTATE code
+pub fn violates(err: &sqlx::Error, code: &str) -> bool {
+    let sqlstate = err.as_database_error().and_then(|err| err.code());
+    return sqlstate.as_deref() == Some(code);
+}
+
+// The name of the constraint the error violates, e.g. "user_kommun_id_fkey"
+pub fn violated_constraint(err: &sqlx::Error) -> Option<&str> {
+    return err.as_database_error().and_then(|err| err.constraint());
+}
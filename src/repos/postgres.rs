@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{BookingRepo, CategoryRepo, CourseRepo, HealthRepo, IdempotencyRepo, LocationRepo};
+use crate::{
+    models::{
+        api::{
+            CreateBookingRequest, CreateCategoryRequest, CreateCityRequest, CreateCourseRequest,
+            CreateDistrictRequest, CreateSubcategoryRequest, MarkAttendanceRequest,
+        },
+        db::{
+            Category, CategoryTreeNode, CertificateInfo, Course, CourseAttendanceInfo,
+            CourseBookingInfo, DistrictCities, IdempotentResponse, Location,
+        },
+    },
+    queries::{
+        query_add_course, query_book_course, query_claim_idempotency_key,
+        query_count_courses_in_category, query_count_courses_in_location, query_create_category,
+        query_create_certificates, query_create_city, query_create_district,
+        query_create_subcategory, query_delete_category, query_delete_location,
+        query_get_all_courses, query_get_category_by_id, query_get_category_by_name,
+        query_get_category_by_slug, query_get_category_descendant_ids, query_get_category_path,
+        query_get_category_slugs_like, query_get_category_tree, query_get_certificate_by_code,
+        query_get_certificates_by_course, query_get_cities_by_district, query_get_city_by_code,
+        query_get_city_by_name, query_get_course_attendance_info, query_get_course_booking_info,
+        query_get_course_by_id, query_get_course_by_name, query_get_courses_by_category,
+        query_get_district_by_code, query_get_district_by_id, query_get_district_by_name,
+        query_get_districts, query_get_districts_cities_tree, query_get_kommuner,
+        query_get_location_by_id, query_get_location_by_slug, query_get_location_slugs_like,
+        query_get_schema_version, query_get_subcategories_by_categoryid,
+        query_get_subcategory_by_name, query_insert_location, query_mark_attendance,
+        query_release_idempotency_key, query_set_course_image, query_store_idempotent_response,
+        query_update_category, query_update_location,
+    },
+};
+
+// Every repository backed by Postgres, running the queries in queries.rs
+#[derive(Clone)]
+pub struct PgRepo {
+    db: PgPool,
+}
+
+impl PgRepo {
+    pub fn new(db: PgPool) -> Self {
+        return PgRepo { db };
+    }
+}
+
+#[async_trait]
+impl CourseRepo for PgRepo {
+    async fn get_all_courses(&self) -> Result<Vec<Course>, sqlx::Error> {
+        return query_get_all_courses(&self.db).await;
+    }
+
+    async fn get_course_by_id(&self, id: &Uuid) -> Result<Course, sqlx::Error> {
+        return query_get_course_by_id(&self.db, id).await;
+    }
+
+    async fn get_course_by_name(&self, name: &str) -> Result<Course, sqlx::Error> {
+        return query_get_course_by_name(&self.db, name).await;
+    }
+
+    async fn get_courses_by_category(
+        &self,
+        category_id: &Uuid,
+    ) -> Result<Vec<Course>, sqlx::Error> {
+        return query_get_courses_by_category(&self.db, category_id).await;
+    }
+
+    async fn add_course(
+        &self,
+        id: &Uuid,
+        start_date: &DateTime<FixedOffset>,
+        end_date: &DateTime<FixedOffset>,
+        course: &CreateCourseRequest,
+    ) -> Result<(), sqlx::Error> {
+        return query_add_course(&self.db, id, start_date, end_date, course).await;
+    }
+
+    async fn set_course_image(&self, id: &Uuid, image: &str) -> Result<(), sqlx::Error> {
+        return query_set_course_image(&self.db, id, image).await;
+    }
+}
+
+#[async_trait]
+impl BookingRepo for PgRepo {
+    async fn get_course_booking_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<CourseBookingInfo, sqlx::Error> {
+        return query_get_course_booking_info(&self.db, course_id).await;
+    }
+
+    async fn book_course(
+        &self,
+        user_id: &Uuid,
+        city: &str,
+        kommun: &Location,
+        booking: &CreateBookingRequest,
+    ) -> Result<(), sqlx::Error> {
+        return query_book_course(&self.db, user_id, city, kommun, booking).await;
+    }
+
+    async fn get_course_attendance_info(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CourseAttendanceInfo>, sqlx::Error> {
+        return query_get_course_attendance_info(&self.db, course_id).await;
+    }
+
+    async fn mark_attendance(
+        &self,
+        course_id: &Uuid,
+        attendance: &MarkAttendanceRequest,
+    ) -> Result<(), sqlx::Error> {
+        return query_mark_attendance(&self.db, course_id, attendance).await;
+    }
+
+    async fn create_certificates(
+        &self,
+        booking_ids: &[Uuid],
+        issuer: &str,
+    ) -> Result<(), sqlx::Error> {
+        return query_create_certificates(&self.db, booking_ids, issuer).await;
+    }
+
+    async fn get_certificates_by_course(
+        &self,
+        course_id: &Uuid,
+    ) -> Result<Vec<CertificateInfo>, sqlx::Error> {
+        return query_get_certificates_by_course(&self.db, course_id).await;
+    }
+
+    async fn get_certificate_by_code(&self, code: &str) -> Result<CertificateInfo, sqlx::Error> {
+        return query_get_certificate_by_code(&self.db, code).await;
+    }
+}
+
+#[async_trait]
+impl LocationRepo for PgRepo {
+    async fn get_districts_cities_tree(&self) -> Result<Vec<DistrictCities>, sqlx::Error> {
+        return query_get_districts_cities_tree(&self.db).await;
+    }
+
+    async fn get_districts(&self) -> Result<Vec<Location>, sqlx::Error> {
+        return query_get_districts(&self.db).await;
+    }
+
+    async fn get_district_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error> {
+        return query_get_district_by_id(&self.db, id).await;
+    }
+
+    async fn get_district_by_name(&self, name: &str) -> Result<Location, sqlx::Error> {
+        return query_get_district_by_name(&self.db, name).await;
+    }
+
+    async fn get_district_by_code(&self, code: &i32) -> Result<Location, sqlx::Error> {
+        return query_get_district_by_code(&self.db, code).await;
+    }
+
+    async fn get_cities_by_district(&self, parent_id: &Uuid) -> Result<Vec<Location>, sqlx::Error> {
+        return query_get_cities_by_district(&self.db, parent_id).await;
+    }
+
+    async fn get_city_by_name(
+        &self,
+        parent_id: &Uuid,
+        name: &str,
+    ) -> Result<Location, sqlx::Error> {
+        return query_get_city_by_name(&self.db, parent_id, name).await;
+    }
+
+    async fn get_city_by_code(
+        &self,
+        parent_id: &Uuid,
+        code: &i32,
+    ) -> Result<Location, sqlx::Error> {
+        return query_get_city_by_code(&self.db, parent_id, code).await;
+    }
+
+    async fn get_kommuner(&self) -> Result<Vec<Location>, sqlx::Error> {
+        return query_get_kommuner(&self.db).await;
+    }
+
+    async fn get_location_by_id(&self, id: &Uuid) -> Result<Location, sqlx::Error> {
+        return query_get_location_by_id(&self.db, id).await;
+    }
+
+    async fn get_location_by_slug(&self, slug: &str) -> Result<Location, sqlx::Error> {
+        return query_get_location_by_slug(&self.db, slug).await;
+    }
+
+    async fn get_location_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error> {
+        return query_get_location_slugs_like(&self.db, base).await;
+    }
+
+    async fn count_courses_in_location(&self, id: &Uuid) -> Result<i64, sqlx::Error> {
+        return query_count_courses_in_location(&self.db, id).await;
+    }
+
+    async fn create_district(
+        &self,
+        id: Uuid,
+        slug: &str,
+        district: &CreateDistrictRequest,
+    ) -> Result<Location, sqlx::Error> {
+        return query_create_district(&self.db, id, slug, district).await;
+    }
+
+    async fn create_city(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        city: &CreateCityRequest,
+    ) -> Result<Location, sqlx::Error> {
+        return query_create_city(&self.db, id, parent_id, slug, city).await;
+    }
+
+    async fn insert_location(
+        &self,
+        id: &Uuid,
+        name: &str,
+        parent_id: &Option<Uuid>,
+        code: &i32,
+        slug: &str,
+    ) -> Result<Location, sqlx::Error> {
+        return query_insert_location(&self.db, id, name, parent_id, code, slug).await;
+    }
+
+    async fn update_location(&self, location: &Location) -> Result<Location, sqlx::Error> {
+        return query_update_location(&self.db, location).await;
+    }
+
+    async fn delete_location(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        return query_delete_location(&self.db, id).await;
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for PgRepo {
+    async fn get_category_tree(&self) -> Result<Vec<CategoryTreeNode>, sqlx::Error> {
+        return query_get_category_tree(&self.db).await;
+    }
+
+    async fn get_category_by_id(&self, category_id: &Uuid) -> Result<Category, sqlx::Error> {
+        return query_get_category_by_id(&self.db, category_id).await;
+    }
+
+    async fn get_category_by_name(&self, name: &str) -> Result<Category, sqlx::Error> {
+        return query_get_category_by_name(&self.db, name).await;
+    }
+
+    async fn get_subcategory_by_name(
+        &self,
+        parent_id: &Uuid,
+        name: &str,
+    ) -> Result<Category, sqlx::Error> {
+        return query_get_subcategory_by_name(&self.db, parent_id, name).await;
+    }
+
+    async fn get_subcategories_by_categoryid(
+        &self,
+        category_id: &Uuid,
+    ) -> Result<Vec<Category>, sqlx::Error> {
+        return query_get_subcategories_by_categoryid(&self.db, category_id).await;
+    }
+
+    async fn get_category_by_slug(&self, slug: &str) -> Result<Category, sqlx::Error> {
+        return query_get_category_by_slug(&self.db, slug).await;
+    }
+
+    async fn get_category_slugs_like(&self, base: &str) -> Result<Vec<String>, sqlx::Error> {
+        return query_get_category_slugs_like(&self.db, base).await;
+    }
+
+    async fn get_category_path(&self, id: &Uuid) -> Result<Vec<Category>, sqlx::Error> {
+        return query_get_category_path(&self.db, id).await;
+    }
+
+    async fn get_category_descendant_ids(&self, id: &Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        return query_get_category_descendant_ids(&self.db, id).await;
+    }
+
+    async fn count_courses_in_category(&self, id: &Uuid) -> Result<i64, sqlx::Error> {
+        return query_count_courses_in_category(&self.db, id).await;
+    }
+
+    async fn create_category(
+        &self,
+        id: &Uuid,
+        slug: &str,
+        category: &CreateCategoryRequest,
+    ) -> Result<Category, sqlx::Error> {
+        return query_create_category(&self.db, id, slug, category).await;
+    }
+
+    async fn create_subcategory(
+        &self,
+        id: &Uuid,
+        parent_id: &Uuid,
+        slug: &str,
+        subcategory: &CreateSubcategoryRequest,
+    ) -> Result<Category, sqlx::Error> {
+        return query_create_subcategory(&self.db, id, parent_id, slug, subcategory).await;
+    }
+
+    async fn update_category(&self, category: &Category) -> Result<Category, sqlx::Error> {
+        return query_update_category(&self.db, category).await;
+    }
+
+    async fn delete_category(
+        &self,
+        id: &Uuid,
+        reassign_to: &Option<Uuid>,
+    ) -> Result<(), sqlx::Error> {
+        return query_delete_category(&self.db, id, reassign_to).await;
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepo for PgRepo {
+    async fn claim_idempotency_key(
+        &self,
+        operation: &str,
+        key: &str,
+        request_hash: &str,
+        expired_before: &DateTime<Utc>,
+        stale_before: &DateTime<Utc>,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error> {
+        return query_claim_idempotency_key(
+            &self.db,
+            operation,
+            key,
+            request_hash,
+            expired_before,
+            stale_before,
+        )
+        .await;
+    }
+
+    async fn store_idempotent_response(
+        &self,
+        operation: &str,
+        key: &str,
+        status: i32,
+        body: &str,
+        location: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        return query_store_idempotent_response(&self.db, operation, key, status, body, location)
+            .await;
+    }
+
+    async fn release_idempotency_key(&self, operation: &str, key: &str) -> Result<(), sqlx::Error> {
+        return query_release_idempotency_key(&self.db, operation, key).await;
+    }
+}
+
+#[async_trait]
+impl HealthRepo for PgRepo {
+    async fn get_schema_version(&self) -> Result<Option<i32>, sqlx::Error> {
+        return query_get_schema_version(&self.db).await;
+    }
+}
@@ -0,0 +1,326 @@
+use std::{future::Future, time::Duration};
+
+use actix_cors::Cors;
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{header, Method},
+    middleware::DefaultHeaders,
+    rt::time::timeout,
+    web::Data,
+    Error, HttpResponse,
+};
+
+use crate::{
+    botcheck::PROOF_HEADER,
+    envelope,
+    idempotency::{KEY_HEADER, REPLAYED_HEADER},
+    AppState,
+};
+
+// Who may call the API from a browser, and how much a request may ask of it.
+// Read from the environment when running:
+//   CORS_ALLOWED_ORIGINS     comma separated origins, `*` for any (default none)
+//   CORS_ALLOWED_METHODS     comma separated methods (default GET,POST,PUT,PATCH,DELETE)
+//   JSON_LIMIT_BYTES         largest JSON body accepted (default 65536)
+//   IMAGE_LIMIT_BYTES        largest image upload accepted (default 5242880)
+//   REQUEST_TIMEOUT_SECONDS  time a request may take before it is answered with
+//                            a 503 (default 30)
+pub struct HttpPolicy {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub json_limit: usize,
+    pub image_limit: usize,
+    pub request_timeout: Duration,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> HttpPolicy {
+        return HttpPolicy {
+            allowed_origins: vec![],
+            allowed_methods: vec![
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ],
+            json_limit: 64 * 1024,
+            image_limit: 5 * 1024 * 1024,
+            request_timeout: Duration::from_secs(30),
+        };
+    }
+}
+
+impl HttpPolicy {
+    pub fn from_env() -> HttpPolicy {
+        let default = HttpPolicy::default();
+
+        return HttpPolicy {
+            allowed_origins: list_var("CORS_ALLOWED_ORIGINS").unwrap_or(default.allowed_origins),
+            allowed_methods: list_var("CORS_ALLOWED_METHODS")
+                .map(|methods| {
+                    methods
+                        .iter()
+                        .filter_map(|method| {
+                            Method::from_bytes(method.to_uppercase().as_bytes()).ok()
+                        })
+                        .collect()
+                })
+                .unwrap_or(default.allowed_methods),
+            json_limit: std::env::var("JSON_LIMIT_BYTES")
+                .ok()
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(default.json_limit),
+            image_limit: std::env::var("IMAGE_LIMIT_BYTES")
+                .ok()
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(default.image_limit),
+            request_timeout: std::env::var("REQUEST_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|seconds| seconds.parse().ok())
+                .map_or(default.request_timeout, Duration::from_secs),
+        };
+    }
+}
+
+// The non-empty items of a comma separated variable
+fn list_var(name: &str) -> Option<Vec<String>> {
+    let value = std::env::var(name).ok()?;
+
+    return Some(
+        value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    );
+}
+
+// Requests from other origins are still handled, only without the headers that
+// let a browser read the response. Browsers send Origin on same-origin POSTs as
+// well, e.g. from the Swagger UI, which must work with no origins configured.
+// Preflights from origins not allowed are refused.
+pub fn cors(policy: &HttpPolicy) -> Cors {
+    let mut cors = Cors::default()
+        .block_on_origin_mismatch(false)
+        .allowed_methods(policy.allowed_methods.clone())
+        .allowed_headers([
+            header::ACCEPT,
+            header::CONTENT_TYPE,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            header::HeaderName::from_static(PROOF_HEADER),
+            header::HeaderName::from_static(KEY_HEADER),
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LOCATION,
+            header::HeaderName::from_static("x-request-id"),
+            header::HeaderName::from_static(REPLAYED_HEADER),
+        ])
+        .max_age(3600);
+
+    for origin in &policy.allowed_origins {
+        cors = match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        };
+    }
+
+    return cors;
+}
+
+// Sent with every response
+pub fn security_headers() -> DefaultHeaders {
+    return DefaultHeaders::new()
+        .add((
+            header::STRICT_TRANSPORT_SECURITY,
+            "max-age=31536000; includeSubDomains",
+        ))
+        .add((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .add((header::X_FRAME_OPTIONS, "DENY"));
+}
+
+// Answers with a 503 when handling the request takes longer than the policy allows
+pub fn request_timeout<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let limit = req
+        .app_data::<Data<AppState>>()
+        .map(|state| state.http.request_timeout);
+    let response = srv.call(req);
+
+    return async move {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return response.await,
+        };
+
+        match timeout(limit, response).await {
+            Ok(response) => return response,
+            Err(elapsed) => {
+                tracing::warn!(timeout_seconds = limit.as_secs_f64(), "Request timed out");
+                let response = HttpResponse::ServiceUnavailable()
+                    .json(envelope::error("The request took too long!"));
+                return Err(InternalError::from_response(elapsed, response).into());
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{
+        dev::Service,
+        http::{header, Method, StatusCode},
+        rt::time::sleep,
+        test::{self, TestRequest},
+        web::{self, Data},
+        App, HttpResponse,
+    };
+    use serde_json::json;
+
+    use super::HttpPolicy;
+    use crate::{
+        tests::{send, test_app},
+        AppState,
+    };
+
+    fn state_with(policy: HttpPolicy) -> Data<AppState> {
+        let mut state = AppState::in_memory();
+        state.http = policy;
+
+        return Data::new(state);
+    }
+
+    #[actix_web::test]
+    async fn allows_configured_origins_only() {
+        let state = state_with(HttpPolicy {
+            allowed_origins: vec![String::from("https://ibnrushd.se")],
+            ..HttpPolicy::default()
+        });
+        let app = test_app!(state);
+
+        let preflight = |origin: &'static str| {
+            TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri("/api/v1/bookings")
+                .insert_header((header::ORIGIN, origin))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+                .to_request()
+        };
+
+        let response = test::call_service(&app, preflight("https://ibnrushd.se")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://ibnrushd.se"
+        );
+
+        let status = match app.call(preflight("https://example.com")).await {
+            Ok(response) => response.status(),
+            Err(err) => err.as_response_error().status_code(),
+        };
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn handles_same_origin_requests_without_configured_origins() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+
+        let request = TestRequest::post()
+            .uri("/api/v1/categories")
+            .insert_header((header::ORIGIN, "http://localhost:8080"))
+            .set_json(json!({ "category_name": "Arabiska" }));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[actix_web::test]
+    async fn sends_security_headers() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+
+        let response = test::call_service(
+            &app,
+            TestRequest::get().uri("/api/v1/locations").to_request(),
+        )
+        .await;
+
+        let headers = response.headers();
+        assert!(headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+        assert_eq!(
+            headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "DENY");
+    }
+
+    #[actix_web::test]
+    async fn rejects_json_bodies_over_the_limit() {
+        let state = state_with(HttpPolicy {
+            json_limit: 1024,
+            ..HttpPolicy::default()
+        });
+        let app = test_app!(state);
+
+        let request = TestRequest::post()
+            .uri("/api/v1/categories")
+            .set_json(json!({ "category_name": "a".repeat(2048) }));
+
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["message"], "Request body is larger than 1024 bytes!");
+    }
+
+    #[actix_web::test]
+    async fn times_out_slow_requests() {
+        let state = state_with(HttpPolicy {
+            request_timeout: Duration::from_millis(10),
+            ..HttpPolicy::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .wrap_fn(super::request_timeout)
+                .route(
+                    "/slow",
+                    web::get().to(|| async {
+                        sleep(Duration::from_millis(500)).await;
+                        return HttpResponse::Ok().json("Done!");
+                    }),
+                ),
+        )
+        .await;
+
+        // The timeout is an error for the server to turn into the response
+        let err = match app.call(TestRequest::get().uri("/slow").to_request()).await {
+            Ok(_) => panic!("The request was not timed out"),
+            Err(err) => err,
+        };
+        let response = err.error_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            r#"{"error":{"message":"The request took too long!"}}"#
+        );
+    }
+}
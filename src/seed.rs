@@ -0,0 +1,130 @@
+use actix_web::web::Data;
+use uuid::Uuid;
+
+use crate::{models::db::Location, services::locations::new_location_slug, AppState};
+
+// Län and kommun with their official SCB codes. Two digit codes are län,
+// four digit codes are kommun belonging to the län listed above them.
+const SCB_LOCATIONS: &str = include_str!("../data/scb_locations.csv");
+
+pub struct ScbLocation {
+    pub code: i32,
+    pub name: String,
+    pub kommuner: Vec<ScbLocation>,
+}
+
+#[derive(Default)]
+pub struct SeedSummary {
+    pub created: u32,
+    pub updated: u32,
+    pub unchanged: u32,
+}
+
+pub fn parse_scb_locations() -> Vec<ScbLocation> {
+    let mut lan: Vec<ScbLocation> = vec![];
+
+    for line in SCB_LOCATIONS.lines().skip(1) {
+        let (code, name) = match line.split_once(',') {
+            Some(row) => row,
+            None => continue,
+        };
+
+        let location = ScbLocation {
+            code: code.parse().expect("Invalid SCB code in dataset"),
+            name: name.to_string(),
+            kommuner: vec![],
+        };
+
+        if code.len() == 2 {
+            lan.push(location);
+        } else {
+            lan.last_mut()
+                .expect("Kommun listed before its län in dataset")
+                .kommuner
+                .push(location);
+        }
+    }
+
+    return lan;
+}
+
+fn found(result: Result<Location, sqlx::Error>) -> Result<Option<Location>, sqlx::Error> {
+    return match result {
+        Ok(location) => Ok(Some(location)),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(err) => Err(err),
+    };
+}
+
+// Inserts the location, or brings an existing one in line with the dataset
+async fn upsert_location(
+    state: &Data<AppState>,
+    existing: Option<Location>,
+    scb: &ScbLocation,
+    parent_id: Option<Uuid>,
+    summary: &mut SeedSummary,
+) -> Result<Uuid, sqlx::Error> {
+    match existing {
+        Some(mut location) => {
+            if location.name == scb.name && location.code == scb.code {
+                summary.unchanged += 1;
+            } else {
+                location.name = scb.name.clone();
+                location.code = scb.code;
+                state.locations.update_location(&location).await?;
+                summary.updated += 1;
+            }
+
+            return Ok(location.id);
+        }
+        None => {
+            let id = Uuid::new_v4();
+            let slug = new_location_slug(state, &scb.name).await?;
+
+            state
+                .locations
+                .insert_location(&id, &scb.name, &parent_id, &scb.code, &slug)
+                .await?;
+            summary.created += 1;
+
+            return Ok(id);
+        }
+    }
+}
+
+// Safe to run repeatedly. Locations are matched on name first and on code
+// second, so districts and cities typed in by hand are reused. Going by name
+// first means a rename never collides with another row of the same name.
+pub async fn seed_locations(state: &Data<AppState>) -> Result<SeedSummary, sqlx::Error> {
+    let mut summary = SeedSummary::default();
+
+    for lan in parse_scb_locations() {
+        let existing = match found(state.locations.get_district_by_name(&lan.name).await)? {
+            Some(district) => Some(district),
+            None => found(state.locations.get_district_by_code(&lan.code).await)?,
+        };
+
+        let district_id = upsert_location(state, existing, &lan, None, &mut summary).await?;
+
+        for kommun in &lan.kommuner {
+            let existing = match found(
+                state
+                    .locations
+                    .get_city_by_name(&district_id, &kommun.name)
+                    .await,
+            )? {
+                Some(city) => Some(city),
+                None => found(
+                    state
+                        .locations
+                        .get_city_by_code(&district_id, &kommun.code)
+                        .await,
+                )?,
+            };
+
+            upsert_location(state, existing, kommun, Some(district_id), &mut summary).await?;
+        }
+    }
+
+    return Ok(summary);
+}
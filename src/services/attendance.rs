@@ -0,0 +1,185 @@
+use crate::{
+    envelope::{data, error, Envelope, ErrorEnvelope},
+    helpers::{build_participant_attendance, sessions_held},
+    models::{
+        api::MarkAttendanceRequest,
+        db::{CourseAttendance, ParticipantAttendance},
+        ids::{BookingId, CourseId},
+    },
+    telemetry::{internal_error, not_found_or_internal_error},
+    validation::Json,
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+use sqlx;
+use uuid::Uuid;
+
+// Attendance per course session
+resources! {
+    "/courses/{id}/attendance" => {
+        post: mark_attendance,
+        get: get_course_attendance,
+    },
+    "/courses/{id}/attendance/{booking_id}" => { get: get_participant_attendance },
+}
+
+pub async fn fetch_course_attendance(
+    state: &Data<AppState>,
+    course_id: &Uuid,
+    sessions: i32,
+) -> Result<CourseAttendance, sqlx::Error> {
+    let infos = state.bookings.get_course_attendance_info(course_id).await?;
+
+    let held = sessions_held(&infos, sessions);
+
+    let participants = infos
+        .into_iter()
+        .map(|info| build_participant_attendance(info, sessions, &held, state.attendance_threshold))
+        .collect();
+
+    return Ok(CourseAttendance {
+        course_id: *course_id,
+        sessions,
+        sessions_held: held.len() as i32,
+        threshold: state.attendance_threshold,
+        participants,
+    });
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/courses/{id}/attendance",
+    tag = "attendance",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    request_body = MarkAttendanceRequest,
+    responses(
+        (status = 204, description = "Attendance marked"),
+        (status = 400, description = "Session out of range or bookings from another course", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given id", body = ErrorEnvelope),
+        (status = 422, description = "Malformed booking id or status", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn mark_attendance(
+    state: Data<AppState>,
+    path: Path<CourseId>,
+    body: Json<MarkAttendanceRequest>,
+) -> impl Responder {
+    let course_id = path.into_inner();
+
+    let course = match state.courses.get_course_by_id(&course_id).await {
+        Ok(course) => course,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No course with given id found!",
+                "Error fetching course!",
+            )
+        }
+    };
+
+    if body.session < 1 || body.session > course.sessions {
+        return HttpResponse::BadRequest().json(error(format!(
+            "Session must be between 1 and {}!",
+            course.sessions
+        )));
+    }
+
+    match state.bookings.mark_attendance(&course_id, &body).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        // A booking that is not on the course matches no row
+        Err(sqlx::Error::RowNotFound) => {
+            HttpResponse::BadRequest().json(error("All bookings must belong to the given course!"))
+        }
+        Err(err) => internal_error("Error marking attendance!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/courses/{id}/attendance",
+    tag = "attendance",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    responses(
+        (status = 200, description = "Attendance of every participant", body = Envelope<CourseAttendance>),
+        (status = 400, description = "Malformed course id", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_course_attendance(state: Data<AppState>, path: Path<CourseId>) -> impl Responder {
+    let course_id = path.into_inner();
+
+    let course = match state.courses.get_course_by_id(&course_id).await {
+        Ok(course) => course,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No course with given id found!",
+                "Error fetching course!",
+            )
+        }
+    };
+
+    match fetch_course_attendance(&state, &course_id, course.sessions).await {
+        Ok(attendance) => HttpResponse::Ok().json(data(attendance)),
+        Err(err) => internal_error("Error fetching attendance!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/courses/{id}/attendance/{booking_id}",
+    tag = "attendance",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+        ("booking_id" = Uuid, Path, description = "Booking id"),
+    ),
+    responses(
+        (status = 200, description = "Attendance of one participant", body = Envelope<ParticipantAttendance>),
+        (status = 400, description = "Malformed id", body = ErrorEnvelope),
+        (status = 404, description = "No such course or booking", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_participant_attendance(
+    state: Data<AppState>,
+    path: Path<(CourseId, BookingId)>,
+) -> impl Responder {
+    let (course_id, booking_id) = path.into_inner();
+
+    let course = match state.courses.get_course_by_id(&course_id).await {
+        Ok(course) => course,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No course with given id found!",
+                "Error fetching course!",
+            )
+        }
+    };
+
+    let attendance = match fetch_course_attendance(&state, &course_id, course.sessions).await {
+        Ok(attendance) => attendance,
+        Err(err) => return internal_error("Error fetching attendance!", &err),
+    };
+
+    match attendance
+        .participants
+        .into_iter()
+        .find(|participant| participant.booking_id == *booking_id)
+    {
+        Some(participant) => HttpResponse::Ok().json(data(participant)),
+        None => {
+            HttpResponse::NotFound().json(error("No booking with given id found on this course!"))
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use crate::{
+    botcheck::PROOF_HEADER,
+    envelope::{data, error, Envelope, ErrorEnvelope},
+    helpers::{find_city, find_kommun, kommun_with_code, suggest_kommuner, KommunMatch},
+    idempotency::Idempotent,
+    models::{api::CreateBookingRequest, db::BookingReceipt},
+    telemetry::{internal_error, not_found_or_internal_error},
+    validation::{normalize_mobile, validation_error_response, Json},
+    AppState,
+};
+
+use actix_web::{http::header, web::Data, HttpRequest, HttpResponse, Responder};
+use std::time::Duration;
+use uuid::Uuid;
+use validator::Validate;
+
+// Booking a seat on a course
+resources! {
+    "/bookings" [Idempotent::new("bookings")] => { post: create_booking },
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/bookings",
+    tag = "bookings",
+    params(
+        ("X-Booking-Proof" = Option<String>, Header, description = "Captcha token or proof of work nonce, when the server asks for one"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of booking again"),
+    ),
+    request_body = CreateBookingRequest,
+    responses(
+        (status = 201, description = "Booking made, or the participant had already booked the course", body = Envelope<BookingReceipt>),
+        (status = 400, description = "Unknown city or kommun, or a kommun name shared by several kommuner, with suggestions", body = ErrorEnvelope),
+        (status = 403, description = "Missing or invalid X-Booking-Proof", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given course_id", body = ErrorEnvelope),
+        (status = 409, description = "The course is fully booked, or the Idempotency-Key was reused for a different request or its request is still being handled", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 429, description = "Too many booking attempts, with Retry-After", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_booking(
+    state: Data<AppState>,
+    req: HttpRequest,
+    mut body: Json<CreateBookingRequest>,
+) -> impl Responder {
+    let limits = &state.booking_limits;
+    if let Err(retry_after) = limits.per_ip.take(&limits.client_ip(&req)) {
+        state.metrics.bookings_rate_limited.inc();
+        return too_many_attempts(retry_after);
+    }
+
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    if let Err(retry_after) = limits
+        .per_personal_number
+        .take(&body.personal_number.to_string())
+    {
+        state.metrics.bookings_rate_limited.inc();
+        return too_many_attempts(retry_after);
+    }
+
+    let proof = req
+        .headers()
+        .get(PROOF_HEADER)
+        .and_then(|proof| proof.to_str().ok());
+    if !state.bot_check.verify(proof, &body).await {
+        state.metrics.bookings_failed_bot_check.inc();
+        return HttpResponse::Forbidden().json(error("Missing or invalid booking proof!"));
+    }
+
+    if let Some(mobile) = normalize_mobile(&body.mobile) {
+        body.mobile = mobile;
+    }
+
+    // Check if there are free seats remaining in Course
+    let course_booking_info = match state
+        .bookings
+        .get_course_booking_info(&body.course_id)
+        .await
+    {
+        Ok(course_booking_info) => course_booking_info,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "Course does not exist!",
+                "Error retrieving course information",
+            )
+        }
+    };
+
+    if course_booking_info.booking_count >= course_booking_info.max_seats as i64 {
+        state.metrics.bookings_rejected_full.inc();
+        return HttpResponse::Conflict().json(error("The course is fully booked!"));
+    }
+
+    // The kommun and city have to be known ones, see find_kommun and find_city
+    let kommuner = match state.locations.get_kommuner().await {
+        Ok(kommuner) => kommuner,
+        Err(err) => return internal_error("Error retrieving kommuner", &err),
+    };
+
+    let postort = state
+        .postnummer_lookup
+        .lookup(&body.zipcode)
+        .map(|area| area.city);
+
+    // Fall back to the postort of the postnummer when no city was given
+    let city = match body.city.trim() {
+        "" => match postort {
+            Some(postort) => postort,
+            None => {
+                return HttpResponse::BadRequest()
+                    .json(error("City cannot be empty for an unknown postnummer!"))
+            }
+        },
+        city => match find_city(city, postort.as_deref(), &kommuner) {
+            Some(city) => city,
+            None => {
+                let mut response = error(format!("Unknown city \"{}\"!", city));
+                let suggestions = postort
+                    .into_iter()
+                    .chain(suggest_kommuner(city, &kommuner, 5));
+                response.error.suggestions = Some(suggestions.collect());
+                return HttpResponse::BadRequest().json(response);
+            }
+        },
+    };
+
+    let kommun = match find_kommun(&body.kommun, &kommuner) {
+        KommunMatch::Found(kommun) => kommun,
+        KommunMatch::Ambiguous(matches) => {
+            let mut response = error(format!(
+                "There are several kommuner named \"{}\", give its code!",
+                body.kommun.trim()
+            ));
+            response.error.suggestions = Some(matches.into_iter().map(kommun_with_code).collect());
+            return HttpResponse::BadRequest().json(response);
+        }
+        KommunMatch::Unknown => {
+            let mut response = error(format!("Unknown kommun \"{}\"!", body.kommun.trim()));
+            response.error.suggestions = Some(suggest_kommuner(&body.kommun, &kommuner, 5));
+            return HttpResponse::BadRequest().json(response);
+        }
+    };
+
+    // Check if the user already has booked this course earlier. They get the same
+    // answer as a new booking, so nobody can find out who booked a course. Every
+    // rejection of the input has to come before this for the same reason.
+    let personal_numbers = course_booking_info
+        .personal_numbers
+        .into_iter()
+        .filter_map(|x| x)
+        .collect::<Vec<i64>>();
+
+    if personal_numbers.contains(&body.personal_number) {
+        state.metrics.bookings_duplicate.inc();
+        return booking_made(&body);
+    }
+
+    // Add the new user to the database, and create the booking.
+    let user_id = Uuid::new_v4();
+
+    match state
+        .bookings
+        .book_course(&user_id, &city, kommun, &body)
+        .await
+    {
+        Ok(_) => {
+            state.metrics.bookings_created.inc();
+            return booking_made(&body);
+        }
+        Err(err) => return internal_error("Error making booking!", &err),
+    }
+}
+
+// Bookings can't be fetched, so unlike other created resources there is no Location
+fn booking_made(booking: &CreateBookingRequest) -> HttpResponse {
+    return HttpResponse::Created().json(data(BookingReceipt {
+        course_id: *booking.course_id,
+    }));
+}
+
+fn too_many_attempts(retry_after: Duration) -> HttpResponse {
+    return HttpResponse::TooManyRequests()
+        .insert_header((
+            header::RETRY_AFTER,
+            retry_after.as_secs_f64().ceil().to_string(),
+        ))
+        .json(error("Too many booking attempts, try again later!"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        botcheck::{ProofOfWork, PROOF_HEADER},
+        ratelimit::TokenBuckets,
+        tests::{
+            add_course, book, booking_request, course_request, fixtures, id, post, send, test_app,
+        },
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn refuses_bookings_once_the_course_is_full() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+
+        let mut course = course_request(&fixtures, "Arabiska 1");
+        course["max_seats"] = json!(2);
+        let course_id = add_course(&app, course).await;
+
+        book(&app, &course_id, 199001011239).await;
+        book(&app, &course_id, 198502023453).await;
+
+        let (status, body) = post(
+            &app,
+            "/api/v1/bookings",
+            booking_request(&course_id, 197703034566),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "The course is fully booked!");
+    }
+
+    #[actix_web::test]
+    async fn answers_bookings_of_the_same_course_like_new_ones() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        book(&app, &course_id, 199001011239).await;
+
+        let (status, body) = post(
+            &app,
+            "/api/v1/bookings",
+            booking_request(&course_id, 199001011239),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["course_id"], course_id.as_str());
+        assert_eq!(state.metrics.bookings_created.get(), 1);
+        assert_eq!(state.metrics.bookings_duplicate.get(), 1);
+    }
+
+    #[actix_web::test]
+    async fn rejects_bad_input_from_booked_participants_like_from_new_ones() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        book(&app, &course_id, 199001011239).await;
+
+        let mut answers = vec![];
+        for personal_number in [199001011239, 198502023453] {
+            let mut booking = booking_request(&course_id, personal_number);
+            booking["kommun"] = json!("Atlantis");
+            answers.push(post(&app, "/api/v1/bookings", booking.clone()).await);
+
+            booking["kommun"] = json!("Stockholm");
+            booking["zipcode"] = json!("999 99");
+            booking["city"] = json!("");
+            answers.push(post(&app, "/api/v1/bookings", booking).await);
+        }
+
+        assert_eq!(answers[0].0, StatusCode::BAD_REQUEST);
+        assert_eq!(answers[1].0, StatusCode::BAD_REQUEST);
+        assert_eq!(answers[0], answers[2]);
+        assert_eq!(answers[1], answers[3]);
+        assert_eq!(state.metrics.bookings_duplicate.get(), 0);
+    }
+
+    #[actix_web::test]
+    async fn resolves_cities_and_kommuner_against_the_locations() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        // A second Stockholm in another län makes the name ambiguous
+        let (_, district) = post(
+            &app,
+            "/api/v1/districts",
+            json!({ "name": "Uppsala län", "code": 3 }),
+        )
+        .await;
+        let (status, _) = post(
+            &app,
+            "/api/v1/cities",
+            json!({ "name": "Stockholm", "district_id": id(&district), "code": 399 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = post(
+            &app,
+            "/api/v1/bookings",
+            booking_request(&course_id, 199001011239),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["suggestions"],
+            json!(["Stockholm (0180)", "Stockholm (0399)"])
+        );
+
+        let mut booking = booking_request(&course_id, 199001011239);
+        booking["kommun"] = json!("0180");
+        booking["city"] = json!("Atlantis");
+        let (status, body) = post(&app, "/api/v1/bookings", booking.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["message"], "Unknown city \"Atlantis\"!");
+        assert_eq!(body["suggestions"][0], "Stockholm");
+
+        booking["city"] = json!("STOCKHOLM");
+        let (status, _) = post(&app, "/api/v1/bookings", booking).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn limits_attempts_per_personal_number() {
+        let mut state = AppState::in_memory();
+        state.booking_limits.per_personal_number = TokenBuckets::new(1, Duration::from_secs(60));
+        let state = Data::new(state);
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        book(&app, &course_id, 199001011239).await;
+
+        let request = TestRequest::post()
+            .uri("/api/v1/bookings")
+            .set_json(booking_request(&course_id, 199001011239));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "60");
+
+        // Someone else can still book
+        book(&app, &course_id, 198502023453).await;
+    }
+
+    #[actix_web::test]
+    async fn requires_proof_of_work_when_asked_to() {
+        let mut state = AppState::in_memory();
+        state.bot_check = Box::new(ProofOfWork { difficulty: 8 });
+        let state = Data::new(state);
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+        let booking = booking_request(&course_id, 199001011239);
+
+        let (status, _) = post(&app, "/api/v1/bookings", booking.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let nonce = (0..)
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}:199001011239:{}", course_id, nonce));
+                hash[0] == 0
+            })
+            .unwrap();
+
+        let request = TestRequest::post()
+            .uri("/api/v1/bookings")
+            .insert_header((PROOF_HEADER, nonce.to_string()))
+            .set_json(booking);
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["course_id"], course_id.as_str());
+    }
+
+    #[actix_web::test]
+    async fn reports_every_unreadable_field_as_invalid() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        // Not a date of birth, too short, and a wrong checksum
+        for personal_number in [199013011234_i64, 9001011239, 199001011238] {
+            let booking = booking_request(&course_id, personal_number);
+            let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(body["fields"]["personal_number"].is_array());
+        }
+
+        let mut booking = booking_request(&course_id, 199001011239);
+        booking["course_id"] = json!("not-a-uuid");
+        let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["fields"]["course_id"],
+            json!(["Could not parse course_id as a UUID!"])
+        );
+
+        let mut booking = booking_request(&course_id, 199001011239);
+        booking.as_object_mut().unwrap().remove("email");
+        let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"]["email"], json!(["missing field `email`"]));
+
+        // Bodies that aren't an object of fields are still a 400
+        for body in [json!("booking"), json!([1, 2])] {
+            let (status, _) = post(&app, "/api/v1/bookings", body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+}
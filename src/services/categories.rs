@@ -0,0 +1,524 @@
+use crate::{
+    cache::Cached,
+    envelope::{created, data, error, Envelope, ErrorEnvelope},
+    helpers::{build_category_tree, slugify, unique_slug},
+    models::{
+        api::{
+            CreateCategoryRequest, CreateSubcategoryRequest, DeleteCategoryQuery,
+            UpdateCategoryRequest,
+        },
+        db::{Category, NestedCategory},
+        ids::CategoryId,
+    },
+    repos::{violates, FOREIGN_KEY_VIOLATION},
+    telemetry::{internal_error, not_found_or_internal_error},
+    validation::{validation_error_response, Json},
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path, Query},
+    HttpResponse, Responder,
+};
+use sqlx;
+use uuid::Uuid;
+use validator::Validate;
+
+// The category tree: categories, subcategories and their ordering
+resources! {
+    "/categories/{id}/subcategories" [Cached] => { get: get_subcategories_by_category_id },
+    "/categories" [Cached] => {
+        post: create_category,
+        get: get_categories_all,
+    },
+    "/subcategories" [Cached] => { post: create_subcategory },
+    "/categories/{id}" [Cached] => {
+        get: get_category_by_id,
+        patch: update_category,
+        delete: delete_category,
+    },
+    "/categories/{id}/path" [Cached] => { get: get_category_path },
+    "/categories/by-slug/{slug}" [Cached] => { get: get_category_by_slug },
+}
+
+pub async fn fetch_categories_and_subcategories(
+    state: &Data<AppState>,
+) -> Result<Vec<NestedCategory>, sqlx::Error> {
+    let category_tree = state.categories.get_category_tree().await?;
+
+    return Ok(build_category_tree(&category_tree));
+}
+
+// Slug for a new category, made unique with a numeric suffix if needed
+pub async fn new_category_slug(state: &Data<AppState>, name: &str) -> Result<String, sqlx::Error> {
+    let mut base = slugify(name);
+    if base.is_empty() {
+        base = String::from("kategori");
+    }
+
+    let taken = state.categories.get_category_slugs_like(&base).await?;
+
+    return Ok(unique_slug(&base, &taken));
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}/subcategories",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+    ),
+    responses(
+        (status = 200, description = "Direct subcategories of the category", body = Envelope<Vec<Category>>),
+        (status = 400, description = "Malformed category id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_subcategories_by_category_id(
+    state: Data<AppState>,
+    path: Path<CategoryId>,
+) -> impl Responder {
+    let parent_id = path.into_inner();
+
+    match state
+        .categories
+        .get_subcategories_by_categoryid(&parent_id)
+        .await
+    {
+        Ok(subcategories) => HttpResponse::Ok().json(data(subcategories)),
+        Err(err) => internal_error("Error fetching subcategories!", &err),
+    }
+}
+
+// Add a category
+#[utoipa::path(
+    post,
+    path = "/api/v1/categories",
+    tag = "categories",
+    request_body = CreateCategoryRequest,
+    responses(
+        (status = 201, description = "The created category, with its path in Location", body = Envelope<Category>),
+        (status = 409, description = "The category already exists", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_category(
+    state: Data<AppState>,
+    body: Json<CreateCategoryRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    match state
+        .categories
+        .get_category_by_name(&body.category_name)
+        .await
+    {
+        Ok(_) => {
+            return HttpResponse::Conflict().json(error("Category with this name already exists!"))
+        }
+        Err(sqlx::Error::RowNotFound) => {}
+        Err(err) => return internal_error("Error adding category!", &err),
+    }
+
+    let id = Uuid::new_v4();
+    let slug = match new_category_slug(&state, &body.category_name).await {
+        Ok(slug) => slug,
+        Err(err) => return internal_error("Error adding category!", &err),
+    };
+
+    match state.categories.create_category(&id, &slug, &body).await {
+        Ok(category) => created(format!("/api/v1/categories/{}", category.id), category),
+        Err(err) => internal_error("Error adding category!", &err),
+    }
+}
+
+// Add a subcategory
+#[utoipa::path(
+    post,
+    path = "/api/v1/subcategories",
+    tag = "categories",
+    request_body = CreateSubcategoryRequest,
+    responses(
+        (status = 201, description = "The created subcategory, with its path in Location", body = Envelope<Category>),
+        (status = 400, description = "Unknown parent", body = ErrorEnvelope),
+        (status = 409, description = "The parent already has a subcategory with the name", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_subcategory(
+    state: Data<AppState>,
+    body: Json<CreateSubcategoryRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    // Check if category exists
+    let parent_id = body.parent_id;
+
+    match state.categories.get_category_by_id(&parent_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return HttpResponse::BadRequest().json(error("Parent category does not exist!"))
+        }
+        Err(err) => return internal_error("Error adding subcategory!", &err),
+    }
+
+    // Check if subcategory already exists
+
+    match state
+        .categories
+        .get_subcategory_by_name(&parent_id, &body.category_name)
+        .await
+    {
+        Ok(_) => {
+            return HttpResponse::Conflict()
+                .json(error("Subcategory with this name already exists!"))
+        }
+        Err(sqlx::Error::RowNotFound) => {}
+        Err(err) => return internal_error("Error adding subcategory!", &err),
+    }
+
+    // Create subcategory
+
+    let id = Uuid::new_v4();
+    let slug = match new_category_slug(&state, &body.category_name).await {
+        Ok(slug) => slug,
+        Err(err) => return internal_error("Error adding subcategory!", &err),
+    };
+
+    match state
+        .categories
+        .create_subcategory(&id, &parent_id, &slug, &body)
+        .await
+    {
+        Ok(subcategory) => created(
+            format!("/api/v1/categories/{}", subcategory.id),
+            subcategory,
+        ),
+        Err(err) => internal_error("Error adding subcategory!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The full category tree", body = Envelope<Vec<NestedCategory>>),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_categories_all(state: Data<AppState>) -> impl Responder {
+    // match query_get_categories_all(&state).await {
+    //     Ok(categories) => HttpResponse::Ok().json(data(categories)),
+    //     Err(_) => HttpResponse::NotFound().json(error("Error fetching categories!")),
+    // }
+
+    match fetch_categories_and_subcategories(&state).await {
+        Ok(categories) => HttpResponse::Ok().json(data(categories)),
+        Err(err) => internal_error("Error fetching categories!", &err),
+    }
+}
+
+// Rename, move or reorder a category or subcategory
+#[utoipa::path(
+    patch,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+    ),
+    request_body = UpdateCategoryRequest,
+    responses(
+        (status = 200, description = "The updated category", body = Envelope<Category>),
+        (status = 400, description = "Unknown parent, or a category moved below itself", body = ErrorEnvelope),
+        (status = 404, description = "No category with the given id", body = ErrorEnvelope),
+        (status = 409, description = "Another category has the name or slug", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn update_category(
+    state: Data<AppState>,
+    path: Path<CategoryId>,
+    body: Json<UpdateCategoryRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    let id = path.into_inner();
+
+    let mut category = match state.categories.get_category_by_id(&id).await {
+        Ok(category) => category,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No category with given id found!",
+                "Error updating category!",
+            )
+        }
+    };
+
+    let name_changed = body
+        .category_name
+        .as_ref()
+        .is_some_and(|name| *name != category.category_name);
+    let parent_changed = body
+        .parent_id
+        .is_some_and(|parent_id| parent_id.map(Uuid::from) != category.parent_id);
+
+    if let Some(name) = &body.category_name {
+        category.category_name = name.trim().to_string();
+    }
+
+    if let Some(parent_id) = body.parent_id {
+        if let Some(parent_id) = parent_id {
+            match state.categories.get_category_by_id(&parent_id).await {
+                Ok(_) => {}
+                Err(sqlx::Error::RowNotFound) => {
+                    return HttpResponse::BadRequest()
+                        .json(error("Parent category does not exist!"))
+                }
+                Err(err) => return internal_error("Error updating category!", &err),
+            }
+
+            // Moving a category below itself would create a cycle
+            match state.categories.get_category_descendant_ids(&id).await {
+                Ok(descendant_ids) if !descendant_ids.contains(&*parent_id) => {}
+                Ok(_) => {
+                    return HttpResponse::BadRequest().json(error(
+                        "A category cannot be moved below itself or its subcategories!",
+                    ))
+                }
+                Err(err) => return internal_error("Error updating category!", &err),
+            }
+        }
+        category.parent_id = parent_id.map(Uuid::from);
+    }
+
+    if let Some(sort_order) = body.sort_order {
+        category.sort_order = sort_order;
+    }
+
+    if let Some(slug) = &body.slug {
+        match state.categories.get_category_by_slug(slug).await {
+            Ok(existing) if existing.id != *id => {
+                return HttpResponse::Conflict()
+                    .json(error("Category with this slug already exists!"))
+            }
+            Ok(_) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return internal_error("Error updating category!", &err),
+        }
+
+        category.slug = slug.to_string();
+    }
+
+    // Check that the name is still unique among its new siblings
+    if name_changed || parent_changed {
+        let existing = match category.parent_id {
+            Some(parent_id) => {
+                state
+                    .categories
+                    .get_subcategory_by_name(&parent_id, &category.category_name)
+                    .await
+            }
+            None => {
+                state
+                    .categories
+                    .get_category_by_name(&category.category_name)
+                    .await
+            }
+        };
+
+        match existing {
+            Ok(existing) if existing.id != *id => {
+                return HttpResponse::Conflict()
+                    .json(error("Category with this name already exists!"))
+            }
+            Ok(_) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return internal_error("Error updating category!", &err),
+        }
+    }
+
+    match state.categories.update_category(&category).await {
+        Ok(category) => HttpResponse::Ok().json(data(category)),
+        Err(err) => internal_error("Error updating category!", &err),
+    }
+}
+
+// Delete a category and everything below it. Linked courses block the delete
+// unless a subcategory to move them to is given with ?reassign_to=
+#[utoipa::path(
+    delete,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+        DeleteCategoryQuery,
+    ),
+    responses(
+        (status = 204, description = "Category deleted"),
+        (status = 400, description = "Invalid reassign_to", body = ErrorEnvelope),
+        (status = 404, description = "No category with the given id", body = ErrorEnvelope),
+        (status = 409, description = "The category is used by courses and no reassign_to was given", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn delete_category(
+    state: Data<AppState>,
+    path: Path<CategoryId>,
+    query: Query<DeleteCategoryQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+
+    if let Err(err) = state.categories.get_category_by_id(&id).await {
+        return not_found_or_internal_error(
+            &err,
+            "No category with given id found!",
+            "Error deleting category!",
+        );
+    }
+
+    match query.reassign_to {
+        Some(target_id) => {
+            let target = match state.categories.get_category_by_id(&target_id).await {
+                Ok(target) => target,
+                Err(sqlx::Error::RowNotFound) => {
+                    return HttpResponse::BadRequest()
+                        .json(error("Subcategory to reassign to does not exist!"))
+                }
+                Err(err) => return internal_error("Error deleting category!", &err),
+            };
+
+            if target.parent_id.is_none() {
+                return HttpResponse::BadRequest()
+                    .json(error("Courses can only be reassigned to a subcategory!"));
+            }
+
+            match state.categories.get_category_descendant_ids(&id).await {
+                Ok(descendant_ids) if !descendant_ids.contains(&target.id) => {}
+                Ok(_) => {
+                    return HttpResponse::BadRequest().json(error(
+                        "Courses cannot be reassigned to a category that is being deleted!",
+                    ))
+                }
+                Err(err) => return internal_error("Error deleting category!", &err),
+            }
+        }
+        None => {
+            let linked_courses = match state.categories.count_courses_in_category(&id).await {
+                Ok(count) => count,
+                Err(err) => return internal_error("Error deleting category!", &err),
+            };
+
+            if linked_courses > 0 {
+                return HttpResponse::Conflict().json(error(format!(
+                    "The category is used by {} courses! Provide reassign_to to move them to another subcategory.",
+                    linked_courses
+                )));
+            }
+        }
+    }
+
+    match state
+        .categories
+        .delete_category(&id, &query.reassign_to.map(Uuid::from))
+        .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            if violates(&err, FOREIGN_KEY_VIOLATION) {
+                return HttpResponse::Conflict()
+                    .json(error("The category is still used by courses!"));
+            } else {
+                return internal_error("Error deleting category!", &err);
+            }
+        }
+    }
+}
+
+// Breadcrumb from the top level category down to the given category
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}/path",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+    ),
+    responses(
+        (status = 200, description = "Categories from the top level down to the given one", body = Envelope<Vec<Category>>),
+        (status = 400, description = "Malformed category id", body = ErrorEnvelope),
+        (status = 404, description = "No category with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_category_path(state: Data<AppState>, path: Path<CategoryId>) -> impl Responder {
+    let id = path.into_inner();
+
+    match state.categories.get_category_path(&id).await {
+        Ok(categories) if !categories.is_empty() => HttpResponse::Ok().json(data(categories)),
+        Ok(_) => HttpResponse::NotFound().json(error("No category with given id found!")),
+        Err(err) => internal_error("Error fetching category path!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/by-slug/{slug}",
+    tag = "categories",
+    params(
+        ("slug" = String, Path, description = "Category slug"),
+    ),
+    responses(
+        (status = 200, description = "The category", body = Envelope<Category>),
+        (status = 404, description = "No category with the given slug", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_category_by_slug(state: Data<AppState>, path: Path<String>) -> impl Responder {
+    match state
+        .categories
+        .get_category_by_slug(&path.into_inner())
+        .await
+    {
+        Ok(category) => HttpResponse::Ok().json(data(category)),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No category with given slug found!",
+            "Error fetching category!",
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+    ),
+    responses(
+        (status = 200, description = "The category", body = Envelope<Category>),
+        (status = 400, description = "Malformed category id", body = ErrorEnvelope),
+        (status = 404, description = "No category with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_category_by_id(state: Data<AppState>, path: Path<CategoryId>) -> impl Responder {
+    let id = path.into_inner();
+
+    match state.categories.get_category_by_id(&id).await {
+        Ok(category) => HttpResponse::Ok().json(data(category)),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No category with given id found!",
+            "Error fetching category!",
+        ),
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    certificate::render_certificate,
+    envelope::{created, data, error, Envelope, ErrorEnvelope},
+    helpers::normalize_verification_code,
+    models::{
+        db::{CertificateInfo, VerifiedCertificate},
+        ids::CourseId,
+    },
+    services::attendance::fetch_course_attendance,
+    telemetry::{internal_error, not_found_or_internal_error},
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+use sqlx::types::chrono::Utc;
+use uuid::Uuid;
+
+// Course certificates, issued after a course has ended and verifiable by anyone
+resources! {
+    "/courses/{id}/certificates" => {
+        post: create_certificates,
+        get: get_certificates_by_course,
+    },
+    "/certificates/{code}" => { get: verify_certificate },
+    "/certificates/{code}/pdf" => { get: download_certificate },
+}
+
+// Issues certificates to every participant meeting the attendance threshold
+#[utoipa::path(
+    post,
+    path = "/api/v1/courses/{id}/certificates",
+    tag = "certificates",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    responses(
+        (status = 201, description = "Every certificate issued for the course, with their path in Location", body = Envelope<Vec<CertificateInfo>>),
+        (status = 400, description = "The course has not ended yet", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_certificates(state: Data<AppState>, path: Path<CourseId>) -> impl Responder {
+    let course_id = path.into_inner();
+
+    let course = match state.courses.get_course_by_id(&course_id).await {
+        Ok(course) => course,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No course with given id found!",
+                "Error fetching course!",
+            )
+        }
+    };
+
+    if course.end_date > Utc::now() {
+        return HttpResponse::BadRequest().json(error(
+            "Certificates can only be issued after the course has ended!",
+        ));
+    }
+
+    let attendance = match fetch_course_attendance(&state, &course_id, course.sessions).await {
+        Ok(attendance) => attendance,
+        Err(err) => return internal_error("Error fetching attendance!", &err),
+    };
+
+    let booking_ids: Vec<Uuid> = attendance
+        .participants
+        .iter()
+        .filter(|participant| !participant.below_threshold)
+        .map(|participant| participant.booking_id)
+        .collect();
+
+    if let Err(err) = state
+        .bookings
+        .create_certificates(&booking_ids, &state.certificate_issuer)
+        .await
+    {
+        return internal_error("Error issuing certificates!", &err);
+    }
+
+    match state.bookings.get_certificates_by_course(&course_id).await {
+        Ok(certificates) => created(
+            format!("/api/v1/courses/{}/certificates", course_id),
+            certificates,
+        ),
+        Err(err) => internal_error("Error fetching certificates!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/courses/{id}/certificates",
+    tag = "certificates",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    responses(
+        (status = 200, description = "Certificates issued for the course", body = Envelope<Vec<CertificateInfo>>),
+        (status = 400, description = "Malformed course id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_certificates_by_course(
+    state: Data<AppState>,
+    path: Path<CourseId>,
+) -> impl Responder {
+    let course_id = path.into_inner();
+
+    match state.bookings.get_certificates_by_course(&course_id).await {
+        Ok(certificates) => HttpResponse::Ok().json(data(certificates)),
+        Err(err) => internal_error("Error fetching certificates!", &err),
+    }
+}
+
+// Public endpoint used to check that a certificate is genuine
+#[utoipa::path(
+    get,
+    path = "/api/v1/certificates/{code}",
+    tag = "certificates",
+    params(
+        ("code" = String, Path, description = "Verification code printed on the certificate"),
+    ),
+    responses(
+        (status = 200, description = "The certificate is genuine, with who it was issued to and for what", body = Envelope<VerifiedCertificate>),
+        (status = 404, description = "No certificate with the given code", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn verify_certificate(state: Data<AppState>, path: Path<String>) -> impl Responder {
+    let code = normalize_verification_code(&path.into_inner());
+
+    match state.bookings.get_certificate_by_code(&code).await {
+        Ok(certificate) => HttpResponse::Ok().json(data(VerifiedCertificate::from(certificate))),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No certificate with given code found!",
+            "Error fetching certificate!",
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/certificates/{code}/pdf",
+    tag = "certificates",
+    params(
+        ("code" = String, Path, description = "Verification code printed on the certificate"),
+    ),
+    responses(
+        (status = 200, description = "The certificate as a PDF", body = Vec<u8>, content_type = "application/pdf"),
+        (status = 404, description = "No certificate with the given code", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn download_certificate(state: Data<AppState>, path: Path<String>) -> impl Responder {
+    let code = normalize_verification_code(&path.into_inner());
+
+    let certificate = match state.bookings.get_certificate_by_code(&code).await {
+        Ok(certificate) => certificate,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No certificate with given code found!",
+                "Error fetching certificate!",
+            )
+        }
+    };
+
+    return HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header((
+            "Content-Disposition",
+            format!("inline; filename=\"certificate-{}.pdf\"", certificate.code),
+        ))
+        .body(render_certificate(&certificate));
+}
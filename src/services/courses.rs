@@ -0,0 +1,263 @@
+use crate::{
+    cache::Cached,
+    envelope::{created, data, error, Envelope, ErrorEnvelope},
+    idempotency::Idempotent,
+    models::{
+        api::CreateCourseRequest,
+        db::{Course, CoursesCategoriesDistricts},
+        ids::{CategoryId, CourseId},
+    },
+    repos::{violates, FOREIGN_KEY_VIOLATION, UNIQUE_VIOLATION},
+    services::{
+        categories::fetch_categories_and_subcategories, locations::fetch_districts_and_cities,
+    },
+    telemetry::{internal_error, not_found_or_internal_error},
+    validation::{validation_error_response, Json},
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+use sqlx::types::chrono::DateTime;
+use uuid::Uuid;
+use validator::Validate;
+
+// Courses and the catalogue combining them with the category and location trees
+resources! {
+    "/courses" [Cached, Idempotent::new("courses")] => {
+        get: get_courses_all,
+        post: create_course,
+    },
+    "/catalogue" [Cached] => { get: get_courses_with_locations },
+    "/categories/{id}/courses" [Cached] => { get: get_courses_by_subcategory_id },
+    "/courses/{id}" [Cached] => { get: get_courses_by_id },
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/courses",
+    tag = "courses",
+    responses(
+        (status = 200, description = "All courses", body = Envelope<Vec<Course>>),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_courses_all(state: Data<AppState>) -> impl Responder {
+    match state.courses.get_all_courses().await {
+        Ok(courses) => HttpResponse::Ok().json(data(courses)),
+        Err(err) => internal_error("Error fetching courses!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/catalogue",
+    tag = "courses",
+    responses(
+        (status = 200, description = "All courses together with the category and location trees", body = Envelope<CoursesCategoriesDistricts>),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_courses_with_locations(state: Data<AppState>) -> impl Responder {
+    let courses = match state.courses.get_all_courses().await {
+        Ok(courses) => courses,
+        Err(err) => return internal_error("Error fetching courses!", &err),
+    };
+
+    let categories = match fetch_categories_and_subcategories(&state).await {
+        Ok(categories) => categories,
+        Err(err) => return internal_error("Error fetching categories!", &err),
+    };
+
+    let districts = match fetch_districts_and_cities(&state).await {
+        Ok(districts) => districts,
+        Err(err) => return internal_error("Error fetching locations!", &err),
+    };
+
+    let response = CoursesCategoriesDistricts {
+        courses,
+        categories,
+        districts,
+    };
+
+    return HttpResponse::Ok().json(data(response));
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/courses",
+    tag = "courses",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the first response instead of adding the course again"),
+    ),
+    request_body = CreateCourseRequest,
+    responses(
+        (status = 201, description = "The added course, with its path in Location", body = Envelope<Course>),
+        (status = 400, description = "Unknown city or subcategory", body = ErrorEnvelope),
+        (status = 409, description = "A course with the name already exists, or the Idempotency-Key was reused for a different request or its request is still being handled", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_course(
+    state: Data<AppState>,
+    body: Json<CreateCourseRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    // Check if course with this name already exists
+    match state.courses.get_course_by_name(&body.course_name).await {
+        Ok(_) => {
+            return HttpResponse::Conflict().json(error("Course with this name already exists!"))
+        }
+        Err(sqlx::Error::RowNotFound) => {}
+        Err(err) => return internal_error("Error adding course.", &err),
+    }
+
+    // Otherwise create new id, convert dates from string to datetime and query the db
+    let id = Uuid::new_v4();
+
+    let start_date = match DateTime::parse_from_rfc3339(&body.start_date) {
+        Ok(parsed_date) => parsed_date,
+        Err(err) => {
+            tracing::debug!(error = %err, "Could not parse start_date");
+            return HttpResponse::BadRequest().json(error("Could not parse start_date!"));
+        }
+    };
+
+    let end_date = match DateTime::parse_from_rfc3339(&body.end_date) {
+        Ok(parsed_date) => parsed_date,
+        Err(err) => {
+            tracing::debug!(error = %err, "Could not parse end_date");
+            return HttpResponse::BadRequest().json(error("Could not parse end_date!"));
+        }
+    };
+
+    match state
+        .courses
+        .add_course(&id, &start_date, &end_date, &body)
+        .await
+    {
+        Ok(_) => {}
+        Err(err) => {
+            if violates(&err, UNIQUE_VIOLATION) {
+                return HttpResponse::Conflict().json(error("Course already exists!"));
+            } else {
+                if violates(&err, FOREIGN_KEY_VIOLATION) {
+                    return HttpResponse::BadRequest().json(error("Error adding course. Please make sure that the all cities and subcategories provided really exists"));
+                } else {
+                    return internal_error("Error adding course.", &err);
+                }
+            }
+        }
+    }
+
+    match state.courses.get_course_by_id(&id).await {
+        Ok(course) => created(format!("/api/v1/courses/{}", id), course),
+        Err(err) => internal_error("Error fetching the added course.", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories/{id}/courses",
+    tag = "courses",
+    params(
+        ("id" = Uuid, Path, description = "Category id"),
+    ),
+    responses(
+        (status = 200, description = "Courses in the category or any category below it", body = Envelope<Vec<Course>>),
+        (status = 400, description = "Malformed category id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_courses_by_subcategory_id(
+    state: Data<AppState>,
+    path: Path<CategoryId>,
+) -> impl Responder {
+    let id = path.into_inner();
+
+    // Includes courses in every category below the given one
+    match state.courses.get_courses_by_category(&id).await {
+        Ok(courses) => HttpResponse::Ok().json(data(courses)),
+        Err(err) => internal_error("Error fetching courses!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/courses/{id}",
+    tag = "courses",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    responses(
+        (status = 200, description = "The course", body = Envelope<Course>),
+        (status = 400, description = "Malformed course id", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_courses_by_id(state: Data<AppState>, path: Path<CourseId>) -> impl Responder {
+    let id = path.into_inner();
+
+    match state.courses.get_course_by_id(&id).await {
+        Ok(course) => HttpResponse::Ok().json(data(course)),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No course with given id found!",
+            "Error fetching course!",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, web::Data};
+    use uuid::Uuid;
+
+    use crate::{
+        tests::{add_course, course_request, fixtures, get, post, test_app},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn refuses_duplicate_course_names() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+
+        add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+        let (status, body) = post(
+            &app,
+            "/api/v1/courses",
+            course_request(&fixtures, "Arabiska 1"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "Course with this name already exists!");
+
+        let (_, courses) = get(&app, "/api/v1/courses").await;
+        assert_eq!(courses.as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn refuses_courses_in_unknown_cities() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+
+        let mut course = course_request(&fixtures, "Arabiska 1");
+        course["city_ids"] = serde_json::json!([Uuid::new_v4()]);
+        let (status, _) = post(&app, "/api/v1/courses", course).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, courses) = get(&app, "/api/v1/courses").await;
+        assert!(courses.as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    blobstore::valid_key,
+    cache::Cached,
+    envelope::{created, error, Envelope, ErrorEnvelope},
+    images::{self, content_type, ImageError},
+    models::{api::ImageUpload, db::CourseImage, ids::CourseId},
+    telemetry::{internal_error, not_found_or_internal_error},
+    AppState,
+};
+
+use actix_multipart::form::MultipartForm;
+use actix_web::{
+    http::header::{self, EntityTag, IfNoneMatch},
+    web::{self, Bytes, Data, Path},
+    HttpMessage, HttpRequest, HttpResponse, Responder,
+};
+use sha2::{Digest, Sha256};
+
+// Image URLs change with the image, so what they point at never does
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+// Course images, stored through AppState::blobs and served from /images
+resources! {
+    "/courses/{id}/image" [Cached] => { post: upload_course_image },
+    "/images/{key:.*}" => { get: get_image },
+}
+
+fn image_url(key: &str) -> String {
+    return format!("/api/v1/images/{}", key);
+}
+
+// Stores the image in every size and makes the original the course image
+#[utoipa::path(
+    post,
+    path = "/api/v1/courses/{id}/image",
+    tag = "images",
+    params(
+        ("id" = Uuid, Path, description = "Course id"),
+    ),
+    request_body(content = ImageUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Where the image can be fetched in each size, with the original in Location", body = Envelope<CourseImage>),
+        (status = 400, description = "Malformed course id or form", body = ErrorEnvelope),
+        (status = 404, description = "No course with the given id", body = ErrorEnvelope),
+        (status = 413, description = "The image is larger than IMAGE_LIMIT_BYTES", body = ErrorEnvelope),
+        (status = 415, description = "Not a JPEG, PNG or WebP image", body = ErrorEnvelope),
+        (status = 422, description = "The image could not be read", body = ErrorEnvelope),
+        (status = 500, description = "Database or storage error", body = ErrorEnvelope),
+    )
+)]
+pub async fn upload_course_image(
+    state: Data<AppState>,
+    path: Path<CourseId>,
+    form: MultipartForm<ImageUpload>,
+) -> impl Responder {
+    let course_id = path.into_inner();
+
+    if let Err(err) = state.courses.get_course_by_id(&course_id).await {
+        return not_found_or_internal_error(
+            &err,
+            "No course with given id found!",
+            "Error fetching course!",
+        );
+    }
+
+    let upload = form.into_inner().image;
+    let sent_as = upload
+        .content_type
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_default();
+
+    let processed = match web::block(move || images::process(&upload.data, &sent_as)).await {
+        Ok(Ok(processed)) => processed,
+        Ok(Err(ImageError::Unsupported)) => {
+            return HttpResponse::UnsupportedMediaType()
+                .json(error("Images must be JPEG, PNG or WebP!"))
+        }
+        Ok(Err(ImageError::Unreadable(reason))) => {
+            return HttpResponse::UnprocessableEntity()
+                .json(error(format!("Could not read the image: {}", reason)))
+        }
+        Err(err) => return internal_error("Error processing image!", &err),
+    };
+
+    let mut urls = vec![];
+    for variant in processed.variants {
+        let key = format!(
+            "courses/{}/{}/{}",
+            *course_id,
+            processed.hash,
+            variant.file_name()
+        );
+
+        let stored = state
+            .blobs
+            .put(
+                &key,
+                Bytes::from(variant.bytes),
+                content_type(variant.format),
+            )
+            .await;
+        if let Err(err) = stored {
+            return internal_error("Error storing image!", &err);
+        }
+        urls.push(image_url(&key));
+    }
+
+    // In the order images::process made them: original, then SIZES
+    let image = CourseImage {
+        original: urls[0].clone(),
+        large: urls[1].clone(),
+        thumbnail: urls[2].clone(),
+    };
+
+    if let Err(err) = state
+        .courses
+        .set_course_image(&course_id, &image.original)
+        .await
+    {
+        return not_found_or_internal_error(
+            &err,
+            "No course with given id found!",
+            "Error updating course!",
+        );
+    }
+
+    return created(image.original.clone(), image);
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/images/{key}",
+    tag = "images",
+    params(
+        ("key" = String, Path, description = "Where the image is stored, as given when it was uploaded"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the image the client has"),
+    ),
+    responses(
+        (status = 200, description = "The image, cacheable for a year", body = Vec<u8>, content_type = "image/*"),
+        (status = 304, description = "The client has the image already"),
+        (status = 404, description = "No image with the given key", body = ErrorEnvelope),
+        (status = 500, description = "Storage error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_image(
+    state: Data<AppState>,
+    path: Path<String>,
+    request: HttpRequest,
+) -> impl Responder {
+    let key = path.into_inner();
+    if !valid_key(&key) {
+        return HttpResponse::NotFound().json(error("No image with given key found!"));
+    }
+
+    let blob = match state.blobs.get(&key).await {
+        Ok(Some(blob)) => blob,
+        Ok(None) => return HttpResponse::NotFound().json(error("No image with given key found!")),
+        Err(err) => return internal_error("Error fetching image!", &err),
+    };
+
+    let etag = EntityTag::new_strong(format!("{:x}", Sha256::digest(&blob.bytes)));
+    let not_modified = match request.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(etags)) => etags.iter().any(|sent| sent.weak_eq(&etag)),
+        None => false,
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header(header::ETag(etag))
+        .insert_header((header::CACHE_CONTROL, CACHE_CONTROL));
+
+    if not_modified {
+        return response.finish();
+    }
+    return response.content_type(blob.content_type).body(blob.bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::Data,
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        security::HttpPolicy,
+        tests::{add_course, course_request, fixtures, get, png, send, test_app, upload_image},
+        AppState,
+    };
+
+    #[actix_web::test]
+    async fn serves_uploaded_images_for_a_year() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+        let uri = format!("/api/v1/courses/{}/image", course_id);
+
+        let request = upload_image(&uri, &png(1600, 900), "image/png");
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers().get(header::LOCATION).unwrap().clone();
+
+        let (_, course) = get(&app, &format!("/api/v1/courses/{}", course_id)).await;
+        assert_eq!(course["image"], location.to_str().unwrap());
+
+        let response = test::call_service(
+            &app,
+            TestRequest::get()
+                .uri(location.to_str().unwrap())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=31536000, immutable"
+        );
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(test::read_body(response).await, png(1600, 900));
+
+        let request = TestRequest::get()
+            .uri(location.to_str().unwrap())
+            .insert_header((header::IF_NONE_MATCH, etag));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let (status, _) = get(&app, "/api/v1/images/courses/nothing/here.png").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&app, "/api/v1/images/courses/../../secret.png").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn refuses_anything_but_small_enough_images() {
+        let mut state = AppState::in_memory();
+        state.http = HttpPolicy {
+            image_limit: 64 * 1024,
+            ..HttpPolicy::default()
+        };
+        let state = Data::new(state);
+        let app = test_app!(state);
+        let fixtures = fixtures(&app).await;
+        let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+        let uri = format!("/api/v1/courses/{}/image", course_id);
+
+        let request = upload_image(&uri, b"<svg></svg>", "image/svg+xml");
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["message"], "Images must be JPEG, PNG or WebP!");
+
+        // A PNG sent as a JPEG is not trusted either
+        let request = upload_image(&uri, &png(10, 10), "image/jpeg");
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let request = upload_image(&uri, &vec![0; 65 * 1024], "image/png");
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["message"], "Request body is larger than 65536 bytes!");
+
+        let request = upload_image(
+            &format!("/api/v1/courses/{}/image", Uuid::new_v4()),
+            &png(10, 10),
+            "image/png",
+        );
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, course) = get(&app, &format!("/api/v1/courses/{}", course_id)).await;
+        assert_eq!(course["image"], "");
+    }
+}
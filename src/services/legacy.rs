@@ -0,0 +1,199 @@
+use actix_web::{
+    middleware::DefaultHeaders,
+    web::{self, ServiceConfig},
+};
+
+use crate::cache::Cached;
+use crate::idempotency::Idempotent;
+use crate::services::{
+    attendance::{get_course_attendance, get_participant_attendance, mark_attendance},
+    bookings::create_booking,
+    categories::{
+        create_category, create_subcategory, delete_category, get_categories_all,
+        get_category_by_slug, get_category_path, get_subcategories_by_category_id, update_category,
+    },
+    certificates::{
+        create_certificates, download_certificate, get_certificates_by_course, verify_certificate,
+    },
+    courses::{
+        create_course, get_courses_all, get_courses_by_id, get_courses_by_subcategory_id,
+        get_courses_with_locations,
+    },
+    locations::{
+        create_city, create_district, delete_location, get_cities_by_district, get_district_all,
+        get_location_by_id, get_location_by_slug, get_locations_all, update_location,
+    },
+    postnummer::get_postnummer,
+};
+
+// When the unversioned paths were deprecated, as an RFC 9745 timestamp (2026-10-19)
+const DEPRECATED_SINCE: &str = "@1792368000";
+
+// Marks a response as coming from a deprecated path and points to the docs of
+// the versioned API that replaces it
+fn deprecated() -> DefaultHeaders {
+    return DefaultHeaders::new()
+        .add(("Deprecation", DEPRECATED_SINCE))
+        .add(("Link", "</docs/>; rel=\"deprecation\""));
+}
+
+// The paths the API was served on before /api/v1. They behave exactly like
+// their versioned counterparts and are kept until all clients have moved.
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::resource("/courses")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_courses_all)),
+    );
+    cfg.service(
+        web::resource("/coursesWithCategoriesAndLocations")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_courses_with_locations)),
+    );
+    cfg.service(
+        web::resource("/course")
+            .wrap(Cached)
+            .wrap(Idempotent::new("courses"))
+            .wrap(deprecated())
+            .route(web::post().to(create_course)),
+    );
+    cfg.service(
+        web::resource("/courses/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_courses_by_id)),
+    );
+    cfg.service(
+        web::resource("/coursesBySubcategoryId/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_courses_by_subcategory_id)),
+    );
+    cfg.service(
+        web::resource("/booking")
+            .wrap(Idempotent::new("bookings"))
+            .wrap(deprecated())
+            .route(web::post().to(create_booking)),
+    );
+    cfg.service(
+        web::resource("/courses/{id}/attendance")
+            .wrap(deprecated())
+            .route(web::get().to(get_course_attendance))
+            .route(web::post().to(mark_attendance)),
+    );
+    cfg.service(
+        web::resource("/courses/{id}/attendance/{booking_id}")
+            .wrap(deprecated())
+            .route(web::get().to(get_participant_attendance)),
+    );
+    cfg.service(
+        web::resource("/courses/{id}/certificates")
+            .wrap(deprecated())
+            .route(web::get().to(get_certificates_by_course))
+            .route(web::post().to(create_certificates)),
+    );
+    cfg.service(
+        web::resource("/certificates/{code}")
+            .wrap(deprecated())
+            .route(web::get().to(verify_certificate)),
+    );
+    cfg.service(
+        web::resource("/certificates/{code}/pdf")
+            .wrap(deprecated())
+            .route(web::get().to(download_certificate)),
+    );
+    cfg.service(
+        web::resource("/categories")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_categories_all)),
+    );
+    cfg.service(
+        web::resource("/category")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::post().to(create_category)),
+    );
+    cfg.service(
+        web::resource("/subcategory")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::post().to(create_subcategory)),
+    );
+    cfg.service(
+        web::resource("/subcategories/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_subcategories_by_category_id)),
+    );
+    cfg.service(
+        web::resource("/categories/by-slug/{slug}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_category_by_slug)),
+    );
+    cfg.service(
+        web::resource("/categories/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::patch().to(update_category))
+            .route(web::delete().to(delete_category)),
+    );
+    cfg.service(
+        web::resource("/categories/{id}/path")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_category_path)),
+    );
+    cfg.service(
+        web::resource("/locations")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_locations_all)),
+    );
+    cfg.service(
+        web::resource("/locations/by-slug/{slug}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_location_by_slug)),
+    );
+    cfg.service(
+        web::resource("/locations/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_location_by_id))
+            .route(web::patch().to(update_location))
+            .route(web::delete().to(delete_location)),
+    );
+    cfg.service(
+        web::resource("/districts")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_district_all)),
+    );
+    cfg.service(
+        web::resource("/district")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::post().to(create_district)),
+    );
+    cfg.service(
+        web::resource("/cities/{id}")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::get().to(get_cities_by_district)),
+    );
+    cfg.service(
+        web::resource("/city")
+            .wrap(Cached)
+            .wrap(deprecated())
+            .route(web::post().to(create_city)),
+    );
+    cfg.service(
+        web::resource("/postnummer/{postnummer}")
+            .wrap(deprecated())
+            .route(web::get().to(get_postnummer)),
+    );
+}
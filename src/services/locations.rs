@@ -0,0 +1,536 @@
+use crate::{
+    cache::Cached,
+    envelope::{created, data, error, Envelope, ErrorEnvelope},
+    helpers::{city_code_in_district, slugify, unique_slug},
+    models::{
+        api::{CreateCityRequest, CreateDistrictRequest, UpdateLocationRequest},
+        db::{City, District, Location},
+        ids::LocationId,
+    },
+    repos::{violated_constraint, violates, FOREIGN_KEY_VIOLATION, UNIQUE_VIOLATION},
+    telemetry::{internal_error, not_found_or_internal_error},
+    validation::{in_range, validation_error_response, Json},
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+use sqlx;
+use uuid::Uuid;
+use validator::Validate;
+
+// Districts (län) and the cities (kommun) in them
+resources! {
+    "/locations" [Cached] => { get: get_locations_all },
+    "/districts" [Cached] => {
+        post: create_district,
+        get: get_district_all,
+    },
+    "/cities" [Cached] => { post: create_city },
+    "/districts/{id}/cities" [Cached] => { get: get_cities_by_district },
+    "/locations/by-slug/{slug}" [Cached] => { get: get_location_by_slug },
+    "/locations/{id}" [Cached] => {
+        get: get_location_by_id,
+        patch: update_location,
+        delete: delete_location,
+    },
+}
+
+pub async fn new_location_slug(state: &Data<AppState>, name: &str) -> Result<String, sqlx::Error> {
+    let mut base = slugify(name);
+    if base.is_empty() {
+        base = String::from("plats");
+    }
+
+    let taken = state.locations.get_location_slugs_like(&base).await?;
+
+    return Ok(unique_slug(&base, &taken));
+}
+
+pub async fn fetch_districts_and_cities(
+    state: &Data<AppState>,
+) -> Result<Vec<District>, sqlx::Error> {
+    let mut response: Vec<District> = vec![];
+
+    let district_cities = state.locations.get_districts_cities_tree().await?;
+
+    for district in district_cities {
+        let zipped: Vec<(Option<Uuid>, Option<String>, Option<String>)> = district
+            .cities_id
+            .iter()
+            .zip(district.cities_name.iter())
+            .zip(district.cities_slug.iter())
+            .map(|((id, name), slug)| (id.clone(), name.clone(), slug.clone()))
+            .collect();
+
+        let mut cities: Vec<Option<City>> = vec![];
+
+        for element in &zipped {
+            if !element.0.is_none() {
+                let city = City {
+                    city_id: element.0.clone(),
+                    city_name: element.1.clone(),
+                    city_slug: element.2.clone(),
+                };
+
+                cities.push(Some(city));
+            }
+        }
+
+        let distr = District {
+            district_id: district.district_id,
+            district_name: district.district_name,
+            district_slug: district.district_slug,
+            cities,
+        };
+
+        response.push(distr);
+    }
+    return Ok(response);
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations",
+    tag = "locations",
+    responses(
+        (status = 200, description = "All districts with their cities", body = Envelope<Vec<District>>),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_locations_all(state: Data<AppState>) -> impl Responder {
+    match fetch_districts_and_cities(&state).await {
+        Ok(locations) => HttpResponse::Ok().json(data(locations)),
+        Err(err) => internal_error("Error fetching locations!", &err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/districts",
+    tag = "locations",
+    request_body = CreateDistrictRequest,
+    responses(
+        (status = 201, description = "The created district, with its path in Location", body = Envelope<Location>),
+        (status = 409, description = "The district already exists", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_district(
+    state: Data<AppState>,
+    body: Json<CreateDistrictRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    let id = Uuid::new_v4();
+
+    let slug = match new_location_slug(&state, &body.name).await {
+        Ok(slug) => slug,
+        Err(err) => return internal_error("Error adding district!", &err),
+    };
+
+    match state.locations.create_district(id, &slug, &body).await {
+        Ok(location) => created(format!("/api/v1/locations/{}", location.id), location),
+        Err(err) => {
+            if violates(&err, UNIQUE_VIOLATION) {
+                return HttpResponse::Conflict().json(error("The district already exists!"));
+            } else {
+                return internal_error("Error adding district!", &err);
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cities",
+    tag = "locations",
+    request_body = CreateCityRequest,
+    responses(
+        (status = 201, description = "The created city, with its path in Location", body = Envelope<Location>),
+        (status = 400, description = "Unknown district or code outside the district", body = ErrorEnvelope),
+        (status = 409, description = "The district already has a city with the name", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn create_city(state: Data<AppState>, body: Json<CreateCityRequest>) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    let parent_id = body.district_id;
+
+    // Check that the provided district actually exists
+    let district = match state.locations.get_district_by_id(&parent_id).await {
+        Ok(district) => district,
+        Err(sqlx::Error::RowNotFound) => {
+            return HttpResponse::BadRequest().json(error("Parent district does not exist!"))
+        }
+        Err(err) => return internal_error("Error adding city!", &err),
+    };
+
+    // The kommun code has to start with the code of its län
+    if !city_code_in_district(body.code, district.code) {
+        return HttpResponse::BadRequest().json(error(format!(
+            "City code {} does not belong to district code {}!",
+            body.code, district.code
+        )));
+    }
+
+    // Check if city already exists under the same district
+    match state
+        .locations
+        .get_city_by_name(&parent_id, &body.name)
+        .await
+    {
+        Ok(_) => {
+            return HttpResponse::Conflict().json(error(
+                "City with this name already exists in this district!",
+            ))
+        }
+        Err(sqlx::Error::RowNotFound) => {}
+        Err(err) => return internal_error("Error adding city!", &err),
+    }
+
+    // Create the city
+    let id = Uuid::new_v4();
+
+    let slug = match new_location_slug(&state, &body.name).await {
+        Ok(slug) => slug,
+        Err(err) => return internal_error("Error adding city!", &err),
+    };
+
+    match state
+        .locations
+        .create_city(&id, &parent_id, &slug, &body)
+        .await
+    {
+        Ok(location) => created(format!("/api/v1/locations/{}", location.id), location),
+        Err(err) => internal_error("Error adding city!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/districts/{id}/cities",
+    tag = "locations",
+    params(
+        ("id" = Uuid, Path, description = "District id"),
+    ),
+    responses(
+        (status = 200, description = "Cities in the district", body = Envelope<Vec<Location>>),
+        (status = 400, description = "Malformed district id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_cities_by_district(
+    state: Data<AppState>,
+    path: Path<LocationId>,
+) -> impl Responder {
+    let parent_id = path.into_inner();
+
+    match state.locations.get_cities_by_district(&parent_id).await {
+        Ok(cities) => HttpResponse::Ok().json(data(cities)),
+        Err(err) => internal_error("Error fetching cities!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/districts",
+    tag = "locations",
+    responses(
+        (status = 200, description = "All districts", body = Envelope<Vec<Location>>),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_district_all(state: Data<AppState>) -> impl Responder {
+    match state.locations.get_districts().await {
+        Ok(districts) => HttpResponse::Ok().json(data(districts)),
+        Err(err) => internal_error("Error fetching districts!", &err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations/by-slug/{slug}",
+    tag = "locations",
+    params(
+        ("slug" = String, Path, description = "Location slug"),
+    ),
+    responses(
+        (status = 200, description = "The location", body = Envelope<Location>),
+        (status = 404, description = "No location with the given slug", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_location_by_slug(state: Data<AppState>, path: Path<String>) -> impl Responder {
+    match state
+        .locations
+        .get_location_by_slug(&path.into_inner())
+        .await
+    {
+        Ok(location) => HttpResponse::Ok().json(data(location)),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No location with given slug found!",
+            "Error fetching location!",
+        ),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations/{id}",
+    tag = "locations",
+    params(
+        ("id" = Uuid, Path, description = "Location id"),
+    ),
+    responses(
+        (status = 200, description = "The location", body = Envelope<Location>),
+        (status = 400, description = "Malformed location id", body = ErrorEnvelope),
+        (status = 404, description = "No location with the given id", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_location_by_id(state: Data<AppState>, path: Path<LocationId>) -> impl Responder {
+    let id = path.into_inner();
+
+    match state.locations.get_location_by_id(&id).await {
+        Ok(location) => HttpResponse::Ok().json(data(location)),
+        Err(err) => not_found_or_internal_error(
+            &err,
+            "No location with given id found!",
+            "Error fetching location!",
+        ),
+    }
+}
+
+// Rename, recode, reorder or move a district or city
+#[utoipa::path(
+    patch,
+    path = "/api/v1/locations/{id}",
+    tag = "locations",
+    params(
+        ("id" = Uuid, Path, description = "Location id"),
+    ),
+    request_body = UpdateLocationRequest,
+    responses(
+        (status = 200, description = "The updated location", body = Envelope<Location>),
+        (status = 400, description = "Unknown district, a code outside the district or leaving its cities outside it, or a district moved", body = ErrorEnvelope),
+        (status = 404, description = "No location with the given id", body = ErrorEnvelope),
+        (status = 409, description = "Another location has the name or slug", body = ErrorEnvelope),
+        (status = 422, description = "Invalid fields", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn update_location(
+    state: Data<AppState>,
+    path: Path<LocationId>,
+    body: Json<UpdateLocationRequest>,
+) -> impl Responder {
+    if let Err(errors) = body.validate() {
+        return validation_error_response(errors);
+    }
+
+    let id = path.into_inner();
+
+    let mut location = match state.locations.get_location_by_id(&id).await {
+        Ok(location) => location,
+        Err(err) => {
+            return not_found_or_internal_error(
+                &err,
+                "No location with given id found!",
+                "Error updating location!",
+            )
+        }
+    };
+
+    let name_changed = body
+        .name
+        .as_ref()
+        .is_some_and(|name| name.trim() != location.name);
+    let parent_changed = body
+        .district_id
+        .is_some_and(|district_id| Some(*district_id) != location.parent_id);
+
+    if let Some(name) = &body.name {
+        location.name = name.trim().to_string();
+    }
+
+    if let Some(district_id) = body.district_id {
+        if location.parent_id.is_none() {
+            return HttpResponse::BadRequest()
+                .json(error("Only cities can be moved to another district!"));
+        }
+
+        location.parent_id = Some(*district_id);
+    }
+
+    if let Some(code) = body.code {
+        // The ranges of CreateCityRequest and CreateDistrictRequest
+        let (min, max) = match location.parent_id {
+            Some(_) => (100, 9999),
+            None => (1, 99),
+        };
+        if let Err(errors) = in_range("code", code, min, max) {
+            return validation_error_response(errors);
+        }
+
+        location.code = code;
+    }
+
+    // A recoded district has to keep prefixing the codes of its cities
+    if location.parent_id.is_none() && body.code.is_some() {
+        let cities = match state.locations.get_cities_by_district(&id).await {
+            Ok(cities) => cities,
+            Err(err) => return internal_error("Error updating location!", &err),
+        };
+
+        let outside = cities
+            .iter()
+            .find(|city| !city_code_in_district(city.code, location.code));
+        if let Some(city) = outside {
+            return HttpResponse::BadRequest().json(error(format!(
+                "City code {} of {} does not belong to district code {}!",
+                city.code, city.name, location.code
+            )));
+        }
+    }
+
+    // A city has to exist under a district whose code prefixes its own
+    if let Some(parent_id) = location.parent_id {
+        if body.district_id.is_some() || body.code.is_some() {
+            let district = match state.locations.get_district_by_id(&parent_id).await {
+                Ok(district) => district,
+                Err(sqlx::Error::RowNotFound) => {
+                    return HttpResponse::BadRequest()
+                        .json(error("Parent district does not exist!"))
+                }
+                Err(err) => return internal_error("Error updating location!", &err),
+            };
+
+            if !city_code_in_district(location.code, district.code) {
+                return HttpResponse::BadRequest().json(error(format!(
+                    "City code {} does not belong to district code {}!",
+                    location.code, district.code
+                )));
+            }
+        }
+    }
+
+    if let Some(sort_order) = body.sort_order {
+        location.sort_order = sort_order;
+    }
+
+    if let Some(slug) = &body.slug {
+        match state.locations.get_location_by_slug(slug).await {
+            Ok(existing) if existing.id != *id => {
+                return HttpResponse::Conflict()
+                    .json(error("Location with this slug already exists!"))
+            }
+            Ok(_) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return internal_error("Error updating location!", &err),
+        }
+
+        location.slug = slug.to_string();
+    }
+
+    // Names are unique among the districts, and among the cities of a district
+    if name_changed || parent_changed {
+        let existing = match location.parent_id {
+            Some(parent_id) => {
+                state
+                    .locations
+                    .get_city_by_name(&parent_id, &location.name)
+                    .await
+            }
+            None => state.locations.get_district_by_name(&location.name).await,
+        };
+
+        match existing {
+            Ok(existing) if existing.id != *id => {
+                return match location.parent_id {
+                    Some(_) => HttpResponse::Conflict().json(error(
+                        "City with this name already exists in this district!",
+                    )),
+                    None => HttpResponse::Conflict().json(error("The district already exists!")),
+                };
+            }
+            Ok(_) | Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return internal_error("Error updating location!", &err),
+        }
+    }
+
+    match state.locations.update_location(&location).await {
+        Ok(location) => HttpResponse::Ok().json(data(location)),
+        Err(err) => internal_error("Error updating location!", &err),
+    }
+}
+
+// Delete a city, or a district together with its cities. Locations where
+// courses are still given cannot be deleted.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/locations/{id}",
+    tag = "locations",
+    params(
+        ("id" = Uuid, Path, description = "Location id"),
+    ),
+    responses(
+        (status = 204, description = "Location deleted"),
+        (status = 400, description = "Malformed location id", body = ErrorEnvelope),
+        (status = 404, description = "No location with the given id", body = ErrorEnvelope),
+        (status = 409, description = "The location is used by courses or is the kommun of booked participants", body = ErrorEnvelope),
+        (status = 500, description = "Database error", body = ErrorEnvelope),
+    )
+)]
+pub async fn delete_location(state: Data<AppState>, path: Path<LocationId>) -> impl Responder {
+    let id = path.into_inner();
+
+    if let Err(err) = state.locations.get_location_by_id(&id).await {
+        return not_found_or_internal_error(
+            &err,
+            "No location with given id found!",
+            "Error deleting location!",
+        );
+    }
+
+    let linked_courses = match state.locations.count_courses_in_location(&id).await {
+        Ok(count) => count,
+        Err(err) => return internal_error("Error deleting location!", &err),
+    };
+
+    if linked_courses > 0 {
+        return HttpResponse::Conflict().json(error(format!(
+            "The location is used by {} courses! Remove it from the courses before deleting it.",
+            linked_courses
+        )));
+    }
+
+    match state.locations.delete_location(&id).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => {
+            if violates(&err, FOREIGN_KEY_VIOLATION) {
+                let message = match violated_constraint(&err) {
+                    Some("course_location_location_id_fkey") => {
+                        "The location is still used by courses!"
+                    }
+                    Some("user_kommun_id_fkey") => {
+                        "The location is the kommun of booked participants!"
+                    }
+                    _ => "The location is still in use!",
+                };
+                return HttpResponse::Conflict().json(error(message));
+            } else {
+                return internal_error("Error deleting location!", &err);
+            }
+        }
+    }
+}
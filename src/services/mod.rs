@@ -0,0 +1,61 @@
+// Declares a module's `config` from its resources, each with the middleware it
+// is wrapped in and a handler per method, and `ROUTES` listing every method and
+// path in the same table. The OpenAPI test reads ROUTES, so nothing can be
+// registered here without it being checked against the spec.
+macro_rules! resources {
+    ($(
+        $path:literal $([$($wrap:expr),+])? => { $($method:ident: $handler:ident),+ $(,)? }
+    ),+ $(,)?) => {
+        pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
+            $(
+                cfg.service(
+                    actix_web::web::resource($path)
+                        $($(.wrap($wrap))+)?
+                        $(.route(actix_web::web::$method().to($handler)))+,
+                );
+            )+
+        }
+
+        // Every route `config` registers, as (method, path)
+        #[cfg(test)]
+        pub const ROUTES: &[(&str, &str)] = &[$($((stringify!($method), $path)),+),+];
+    };
+}
+
+// Mounts the modules in order and collects their ROUTES
+macro_rules! modules {
+    ($($module:ident),+ $(,)?) => {
+        pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
+            $(cfg.configure($module::config);)+
+        }
+
+        // Every versioned route, without the /api/v1 scope
+        #[cfg(test)]
+        pub fn routes() -> Vec<(&'static str, &'static str)> {
+            return [$($module::ROUTES),+].concat();
+        }
+    };
+}
+
+pub mod attendance;
+pub mod bookings;
+pub mod categories;
+pub mod certificates;
+pub mod courses;
+pub mod images;
+pub mod legacy;
+pub mod locations;
+pub mod postnummer;
+
+// Every versioned route, mounted under /api/v1 in main.rs. Categories go first
+// so that /categories/by-slug/{slug} is matched before any /categories/{id}/...
+modules!(
+    categories,
+    courses,
+    images,
+    bookings,
+    attendance,
+    certificates,
+    locations,
+    postnummer,
+);
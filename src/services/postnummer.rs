@@ -0,0 +1,46 @@
+use crate::{
+    envelope::{data, error, Envelope, ErrorEnvelope},
+    models::db::PostnummerInfo,
+    postnummer::Postnummer,
+    AppState,
+};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpResponse, Responder,
+};
+
+// Postnummer lookup used to fill in addresses
+resources! {
+    "/postnummer/{postnummer}" => { get: get_postnummer },
+}
+
+// Lets the booking form fill in the city as soon as the postnummer is typed
+#[utoipa::path(
+    get,
+    path = "/api/v1/postnummer/{postnummer}",
+    tag = "postnummer",
+    params(
+        ("postnummer" = String, Path, description = "Five digit postnummer, with or without the space"),
+    ),
+    responses(
+        (status = 200, description = "The postort of the postnummer", body = Envelope<PostnummerInfo>),
+        (status = 400, description = "Malformed postnummer", body = ErrorEnvelope),
+        (status = 404, description = "Postnummer not in the dataset", body = ErrorEnvelope),
+    )
+)]
+pub async fn get_postnummer(state: Data<AppState>, path: Path<String>) -> impl Responder {
+    let postnummer: Postnummer = match path.into_inner().parse() {
+        Ok(postnummer) => postnummer,
+        Err(err) => return HttpResponse::BadRequest().json(error(err)),
+    };
+
+    match state.postnummer_lookup.lookup(&postnummer) {
+        Some(area) => HttpResponse::Ok().json(data(PostnummerInfo {
+            postnummer,
+            city: area.city,
+            kommun_code: area.kommun_code,
+        })),
+        None => HttpResponse::NotFound().json(error("No city found for the given postnummer!")),
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    error::Error as StdError,
+    future::Future,
+    io::{self, IsTerminal},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage, HttpResponse,
+};
+use tracing_actix_web::RequestId;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+use crate::envelope;
+
+// Logs go to stdout, one line per event. LOG_LEVEL takes a level or filter
+// directives such as `info,sqlx=debug` (default `info`), LOG_FORMAT=json gives
+// one JSON object per line instead of text. Every request gets a span with its
+// id, and closing it logs how long the request took; statements run by sqlx
+// are logged with their duration at debug level, slow ones at warn.
+pub fn init() {
+    let filter = std::env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(io::stdout().is_terminal());
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber.json().with_current_span(true).init(),
+        _ => subscriber.init(),
+    }
+}
+
+// Returns the id of each request in the X-Request-Id header, to find its logs by
+pub fn request_id_header<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let response = srv.call(req);
+
+    return async move {
+        let mut response = response.await?;
+
+        let request_id = response.request().extensions().get::<RequestId>().copied();
+        if let Some(request_id) = request_id {
+            if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static("x-request-id"), value);
+            }
+        }
+
+        return Ok(response);
+    };
+}
+
+// The error followed by everything that caused it, e.g.
+// "error returned from database: relation \"db.courses\" does not exist"
+pub fn cause_chain(err: &dyn StdError) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    return chain;
+}
+
+// Logs the error with its causes, the client only gets the message
+pub fn internal_error(message: &str, err: &dyn StdError) -> HttpResponse {
+    tracing::error!(error = %cause_chain(err), "{}", message);
+
+    return HttpResponse::InternalServerError().json(envelope::error(message));
+}
+
+// A missing row is a 404 with `not_found`, any other database error is logged
+// and answered as internal_error with `message`
+pub fn not_found_or_internal_error(
+    err: &sqlx::Error,
+    not_found: &str,
+    message: &str,
+) -> HttpResponse {
+    if let sqlx::Error::RowNotFound = err {
+        return HttpResponse::NotFound().json(envelope::error(not_found));
+    }
+
+    return internal_error(message, err);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error as StdError, fmt, io};
+
+    use actix_web::{
+        test::{self, TestRequest},
+        web::Data,
+    };
+
+    use super::cause_chain;
+    use crate::{tests::test_app, AppState};
+
+    #[actix_web::test]
+    async fn returns_the_request_id() {
+        let state = Data::new(AppState::in_memory());
+        let app = test_app!(state);
+
+        let first = test::call_service(
+            &app,
+            TestRequest::get().uri("/api/v1/locations").to_request(),
+        )
+        .await;
+        let second = test::call_service(
+            &app,
+            TestRequest::get().uri("/api/v1/locations").to_request(),
+        )
+        .await;
+
+        let first_id = first.headers().get("x-request-id").unwrap();
+        let second_id = second.headers().get("x-request-id").unwrap();
+        assert_ne!(first_id, second_id);
+    }
+
+    #[derive(Debug)]
+    struct Wrapped(io::Error);
+
+    impl fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            return f.write_str("could not read the dataset");
+        }
+    }
+
+    impl StdError for Wrapped {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            return Some(&self.0);
+        }
+    }
+
+    #[test]
+    fn includes_every_cause() {
+        let err = Wrapped(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+
+        assert_eq!(
+            cause_chain(&err),
+            "could not read the dataset: no such file"
+        );
+    }
+}
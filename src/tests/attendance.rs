@@ -0,0 +1,137 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{add_course, book, course_request, fixtures, get, post, test_app, TestDb};
+
+#[actix_web::test]
+async fn marks_attendance() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+    let present = book(&app, &course_id, 199001011239).await;
+    let absent = book(&app, &course_id, 198502023453).await;
+
+    let uri = format!("/api/v1/courses/{}/attendance", course_id);
+    let (status, body) = post(
+        &app,
+        &uri,
+        json!({
+            "session": 1,
+            "entries": [
+                { "booking_id": present, "status": "present" },
+                { "booking_id": absent, "status": "absent" },
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(body.is_null());
+
+    let (status, attendance) = get(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(attendance["sessions"], 4);
+    assert_eq!(attendance["sessions_held"], 1);
+
+    let (status, participant) = get(&app, &format!("{}/{}", uri, present)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        participant["sessions"],
+        json!(["present", null, null, null])
+    );
+    assert_eq!(participant["attendance_percentage"], 100.0);
+    assert_eq!(participant["below_threshold"], false);
+
+    let (_, participant) = get(&app, &format!("{}/{}", uri, absent)).await;
+    assert_eq!(participant["attendance_percentage"], 0.0);
+    assert_eq!(participant["below_threshold"], true);
+
+    // Marking a session again overwrites the earlier status
+    post(
+        &app,
+        &uri,
+        json!({ "session": 1, "entries": [{ "booking_id": absent, "status": "excused" }] }),
+    )
+    .await;
+
+    let (_, participant) = get(&app, &format!("{}/{}", uri, absent)).await;
+    assert_eq!(participant["sessions"][0], "excused");
+    assert_eq!(participant["attendance_percentage"], 100.0);
+}
+
+#[actix_web::test]
+async fn rejects_sessions_outside_the_course() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+    let booking_id = book(&app, &course_id, 199001011239).await;
+
+    let (status, body) = post(
+        &app,
+        &format!("/api/v1/courses/{}/attendance", course_id),
+        json!({ "session": 5, "entries": [{ "booking_id": booking_id, "status": "present" }] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Session must be between 1 and 4!");
+}
+
+#[actix_web::test]
+async fn rejects_bookings_from_other_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+    let other_course_id = add_course(&app, course_request(&fixtures, "Arabiska 2")).await;
+    let booking_id = book(&app, &course_id, 199001011239).await;
+    let other_booking_id = book(&app, &other_course_id, 198502023453).await;
+
+    let uri = format!("/api/v1/courses/{}/attendance", course_id);
+    let (status, body) = post(
+        &app,
+        &uri,
+        json!({
+            "session": 1,
+            "entries": [
+                { "booking_id": booking_id, "status": "present" },
+                { "booking_id": other_booking_id, "status": "present" },
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "All bookings must belong to the given course!"
+    );
+
+    // The whole request is rolled back
+    let (_, attendance) = get(&app, &uri).await;
+    assert_eq!(attendance["sessions_held"], 0);
+
+    let (status, _) = get(&app, &format!("{}/{}", uri, other_booking_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = get(&app, &format!("{}/not-a-booking", uri)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Could not parse booking_id as a UUID!");
+}
+
+#[actix_web::test]
+async fn rejects_unknown_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let uri = format!("/api/v1/courses/{}/attendance", Uuid::new_v4());
+
+    let (status, _) = get(&app, &uri).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = post(&app, &uri, json!({ "session": 1, "entries": [] })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = get(&app, &format!("{}/{}", uri, Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
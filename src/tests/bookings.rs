@@ -0,0 +1,142 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{add_course, book, booking_request, course_request, fixtures, post, test_app, TestDb};
+
+#[actix_web::test]
+async fn books_a_course() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let mut booking = booking_request(&course_id, 199001011239);
+    booking["city"] = json!("");
+
+    let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["course_id"], course_id.as_str());
+
+    // The city comes from the postnummer and the mobile number is stored normalized
+    let (zipcode, city, kommun_id, mobile): (String, String, Uuid, String) =
+        sqlx::query_as("SELECT zipcode, city, kommun_id, mobile FROM db.user")
+            .fetch_one(&db.db)
+            .await
+            .unwrap();
+    assert_eq!(zipcode, "11330");
+    assert_eq!(city, "Stockholm");
+    assert_eq!(kommun_id.to_string(), fixtures.city_id);
+    assert_eq!(mobile, "+46701234567");
+}
+
+#[actix_web::test]
+async fn does_not_reveal_existing_bookings() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    book(&app, &course_id, 199001011239).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/bookings",
+        booking_request(&course_id, 199001011239),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["course_id"], course_id.as_str());
+
+    let (bookings,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM db.course_bookings")
+        .fetch_one(&db.db)
+        .await
+        .unwrap();
+    assert_eq!(bookings, 1);
+}
+
+#[actix_web::test]
+async fn rejects_bookings_on_full_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let mut course = course_request(&fixtures, "Arabiska 1");
+    course["max_seats"] = json!(1);
+    let course_id = add_course(&app, course).await;
+
+    book(&app, &course_id, 199001011239).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/bookings",
+        booking_request(&course_id, 198502023453),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "The course is fully booked!");
+}
+
+#[actix_web::test]
+async fn rejects_bookings_on_unknown_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    fixtures(&app).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/bookings",
+        booking_request(&Uuid::new_v4().to_string(), 199001011239),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Course does not exist!");
+}
+
+#[actix_web::test]
+async fn suggests_kommuner_for_unknown_ones() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let mut booking = booking_request(&course_id, 199001011239);
+    booking["kommun"] = json!("Stokholm");
+
+    let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["suggestions"], json!(["Stockholm"]));
+}
+
+#[actix_web::test]
+async fn rejects_invalid_bookings() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let mut booking = booking_request(&course_id, 199001011239);
+    booking["email"] = json!("amina");
+    booking["mobile"] = json!("08-123 456");
+
+    let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["fields"]["email"].is_array());
+    assert!(body["fields"]["mobile"].is_array());
+
+    let mut booking = booking_request(&course_id, 199001011239);
+    booking["zipcode"] = json!("1133");
+
+    let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["fields"]["zipcode"],
+        json!(["\"1133\" is not a valid postnummer, expected five digits like \"113 30\""])
+    );
+
+    // The last digit of 199001011234 is not its checksum
+    let booking = booking_request(&course_id, 199001011234);
+    let (status, body) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["fields"]["personal_number"].is_array());
+}
@@ -0,0 +1,288 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{add_course, course_request, delete, fixtures, get, id, patch, post, test_app, TestDb};
+
+#[actix_web::test]
+async fn creates_the_category_tree() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, tree) = get(&app, "/api/v1/categories").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tree[0]["category_slug"], "sprak-kultur");
+    assert_eq!(tree[0]["subcategories"][0]["subcategory_name"], "Arabiska");
+
+    let (status, subcategories) = get(
+        &app,
+        &format!("/api/v1/categories/{}/subcategories", fixtures.category_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(subcategories[0]["id"], fixtures.subcategory_id.as_str());
+
+    let (status, category) = get(&app, "/api/v1/categories/by-slug/arabiska").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(category["parent_id"], fixtures.category_id.as_str());
+
+    let (status, category) = get(
+        &app,
+        &format!("/api/v1/categories/{}", fixtures.subcategory_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(category["slug"], "arabiska");
+
+    let (status, path) = get(
+        &app,
+        &format!("/api/v1/categories/{}/path", fixtures.subcategory_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(path[0]["id"], fixtures.category_id.as_str());
+    assert_eq!(path[1]["id"], fixtures.subcategory_id.as_str());
+}
+
+#[actix_web::test]
+async fn rejects_duplicate_categories() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/categories",
+        json!({ "category_name": "Språk & kultur" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "Category with this name already exists!");
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/subcategories",
+        json!({ "category_name": "Arabiska", "parent_id": fixtures.category_id }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["message"],
+        "Subcategory with this name already exists!"
+    );
+
+    // The same name is fine below another parent, its slug gets a suffix
+    let (_, other) = post(
+        &app,
+        "/api/v1/categories",
+        json!({ "category_name": "Religion" }),
+    )
+    .await;
+    let (status, subcategory) = post(
+        &app,
+        "/api/v1/subcategories",
+        json!({ "category_name": "Arabiska", "parent_id": id(&other) }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(subcategory["slug"], "arabiska-2");
+}
+
+#[actix_web::test]
+async fn rejects_subcategories_of_unknown_categories() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/subcategories",
+        json!({ "category_name": "Arabiska", "parent_id": Uuid::new_v4() }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Parent category does not exist!");
+}
+
+#[actix_web::test]
+async fn updates_categories() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let uri = format!("/api/v1/categories/{}", fixtures.subcategory_id);
+
+    let (status, category) = patch(
+        &app,
+        &uri,
+        json!({ "category_name": "Arabiska för nybörjare", "sort_order": 2 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(category["category_name"], "Arabiska för nybörjare");
+    assert_eq!(category["sort_order"], 2);
+    assert_eq!(category["slug"], "arabiska");
+
+    let (status, category) = patch(&app, &uri, json!({ "parent_id": null })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(category["parent_id"], json!(null));
+
+    let (status, _) = patch(
+        &app,
+        &format!("/api/v1/categories/{}", Uuid::new_v4()),
+        json!({ "sort_order": 1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn rejects_invalid_category_updates() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let uri = format!("/api/v1/categories/{}", fixtures.subcategory_id);
+
+    let (status, body) = patch(
+        &app,
+        &uri,
+        json!({ "category_name": " ", "slug": "Arabiska 1" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["fields"]["category_name"].is_array());
+    assert!(body["fields"]["slug"].is_array());
+
+    let (status, body) = patch(&app, &uri, json!({ "parent_id": "not-a-uuid" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["fields"]["parent_id"],
+        json!(["Could not parse category_id as a UUID!"])
+    );
+
+    let (_, category) = get(&app, &uri).await;
+    assert_eq!(category["category_name"], "Arabiska");
+}
+
+#[actix_web::test]
+async fn rejects_moving_a_category_below_itself() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, body) = patch(
+        &app,
+        &format!("/api/v1/categories/{}", fixtures.category_id),
+        json!({ "parent_id": fixtures.subcategory_id }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "A category cannot be moved below itself or its subcategories!"
+    );
+
+    let (status, body) = patch(
+        &app,
+        &format!("/api/v1/categories/{}", fixtures.subcategory_id),
+        json!({ "parent_id": Uuid::new_v4() }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Parent category does not exist!");
+}
+
+#[actix_web::test]
+async fn deletes_categories_in_use_only_when_reassigned() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let (status, _) = delete(
+        &app,
+        &format!("/api/v1/categories/{}", fixtures.category_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, other) = post(
+        &app,
+        "/api/v1/categories",
+        json!({ "category_name": "Religion" }),
+    )
+    .await;
+    let (_, target) = post(
+        &app,
+        "/api/v1/subcategories",
+        json!({ "category_name": "Koran", "parent_id": id(&other) }),
+    )
+    .await;
+
+    let (status, body) = delete(
+        &app,
+        &format!(
+            "/api/v1/categories/{}?reassign_to={}",
+            fixtures.category_id,
+            id(&target)
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(body.is_null());
+
+    let (_, course) = get(&app, &format!("/api/v1/courses/{}", course_id)).await;
+    assert_eq!(course["subcategory_names"], json!(["Koran"]));
+
+    let (status, _) = get(
+        &app,
+        &format!("/api/v1/categories/{}/path", fixtures.subcategory_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = delete(
+        &app,
+        &format!("/api/v1/categories/{}", fixtures.category_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn serves_the_category_tree_from_the_cache_until_it_changes() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (_, tree) = get(&app, "/api/v1/categories").await;
+    assert_eq!(tree[0]["category_name"], "Språk & kultur");
+
+    // Changes made behind the app's back wait for the cache to expire
+    sqlx::query("UPDATE db.categories SET category_name = 'Språk' WHERE id = $1")
+        .bind(Uuid::parse_str(&fixtures.category_id).unwrap())
+        .execute(&db.db)
+        .await
+        .unwrap();
+    let (_, tree) = get(&app, "/api/v1/categories").await;
+    assert_eq!(tree[0]["category_name"], "Språk & kultur");
+
+    let (status, _) = post(
+        &app,
+        "/api/v1/categories",
+        json!({ "category_name": "Religion" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, tree) = get(&app, "/api/v1/categories").await;
+    let names: Vec<&str> = tree
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|category| category["category_name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"Språk"));
+    assert!(names.contains(&"Religion"));
+}
@@ -0,0 +1,119 @@
+use actix_web::{
+    http::{header, StatusCode},
+    test::{self, TestRequest},
+};
+use serde_json::json;
+
+use super::{add_course, book, course_request, fixtures, get, post, test_app, TestDb};
+
+#[actix_web::test]
+async fn issues_certificates_above_the_threshold() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let mut course = course_request(&fixtures, "Arabiska 1");
+    course["start_date"] = json!("2020-01-06T18:00:00+01:00");
+    course["end_date"] = json!("2020-03-23T20:00:00+01:00");
+    let course_id = add_course(&app, course).await;
+
+    let present = book(&app, &course_id, 199001011239).await;
+    let absent = book(&app, &course_id, 198502023453).await;
+
+    for session in 1..=4 {
+        post(
+            &app,
+            &format!("/api/v1/courses/{}/attendance", course_id),
+            json!({
+                "session": session,
+                "entries": [
+                    { "booking_id": present, "status": "present" },
+                    { "booking_id": absent, "status": "absent" },
+                ],
+            }),
+        )
+        .await;
+    }
+
+    let uri = format!("/api/v1/courses/{}/certificates", course_id);
+    let (status, certificates) = post(&app, &uri, json!(null)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(certificates.as_array().unwrap().len(), 1);
+    assert_eq!(certificates[0]["booking_id"], present.as_str());
+
+    // Issuing again keeps the codes already handed out
+    let (_, again) = post(&app, &uri, json!(null)).await;
+    assert_eq!(again, certificates);
+
+    let (status, listed) = get(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, certificates);
+
+    let code = certificates[0]["code"].as_str().unwrap();
+
+    let (status, certificate) = get(
+        &app,
+        &format!("/api/v1/certificates/{}", code.to_lowercase()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(certificate["course_name"], "Arabiska 1");
+    assert_eq!(certificate["issuer"], certificates[0]["issuer"]);
+
+    // Only what the certificate shows, not the booking or course behind it
+    let mut fields: Vec<&String> = certificate.as_object().unwrap().keys().collect();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "course_name",
+            "first_name",
+            "issued_at",
+            "issuer",
+            "last_name"
+        ]
+    );
+
+    let request = TestRequest::get()
+        .uri(&format!("/api/v1/certificates/{}/pdf", code))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/pdf"
+    );
+    assert!(test::read_body(response).await.starts_with(b"%PDF"));
+}
+
+#[actix_web::test]
+async fn waits_for_the_course_to_end() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let (status, body) = post(
+        &app,
+        &format!("/api/v1/courses/{}/certificates", course_id),
+        json!(null),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "Certificates can only be issued after the course has ended!"
+    );
+}
+
+#[actix_web::test]
+async fn rejects_unknown_codes() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, _) = get(&app, "/api/v1/certificates/0000-0000-0000").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = get(&app, "/api/v1/certificates/0000-0000-0000/pdf").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,203 @@
+use actix_web::{
+    http::{header, StatusCode},
+    test::{self, TestRequest},
+};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::{add_course, course_request, fixtures, get, post, test_app, TestDb};
+
+#[actix_web::test]
+async fn creates_and_lists_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let request = TestRequest::post()
+        .uri("/api/v1/courses")
+        .set_json(course_request(&fixtures, "Arabiska 1"));
+    let response = test::call_service(&app, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = response.headers().get(header::LOCATION).unwrap().clone();
+    let created: Value = test::read_body_json(response).await;
+    let course_id = created["data"]["id"].as_str().unwrap();
+    assert_eq!(location, format!("/api/v1/courses/{}", course_id).as_str());
+
+    let (status, course) = get(&app, location.to_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(course, created["data"]);
+    assert_eq!(course["course_name"], "Arabiska 1");
+    assert_eq!(course["city_names"], json!(["Stockholm"]));
+    assert_eq!(course["subcategory_names"], json!(["Arabiska"]));
+
+    let (status, catalogue) = get(&app, "/api/v1/catalogue").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(catalogue["courses"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        catalogue["categories"][0]["category_name"],
+        "Språk & kultur"
+    );
+    assert_eq!(
+        catalogue["districts"][0]["cities"][0]["city_name"],
+        "Stockholm"
+    );
+}
+
+#[actix_web::test]
+async fn lists_nothing_as_empty_arrays() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    for uri in [
+        "/api/v1/courses",
+        "/api/v1/categories",
+        "/api/v1/districts",
+        "/api/v1/locations",
+    ] {
+        let (status, listed) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::OK, "{}", uri);
+        assert_eq!(listed, json!([]), "{}", uri);
+    }
+
+    let (status, catalogue) = get(&app, "/api/v1/catalogue").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(catalogue["courses"], json!([]));
+}
+
+#[actix_web::test]
+async fn rejects_duplicate_course_names() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/courses",
+        course_request(&fixtures, "Arabiska 1"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "Course with this name already exists!");
+
+    let (_, courses) = get(&app, "/api/v1/courses").await;
+    assert_eq!(courses.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn rejects_unknown_cities_and_subcategories() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let mut course = course_request(&fixtures, "Arabiska 1");
+    course["city_ids"] = json!([Uuid::new_v4()]);
+
+    let (status, _) = post(&app, "/api/v1/courses", course).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut course = course_request(&fixtures, "Arabiska 1");
+    course["subcategory_ids"] = json!([Uuid::new_v4()]);
+
+    let (status, _) = post(&app, "/api/v1/courses", course).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Nothing is left behind by the rolled back inserts
+    let (_, courses) = get(&app, "/api/v1/courses").await;
+    assert_eq!(courses, json!([]));
+}
+
+#[actix_web::test]
+async fn rejects_invalid_courses() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let mut course = course_request(&fixtures, " ");
+    course["max_seats"] = json!(0);
+    course["end_date"] = json!("2029-01-01T00:00:00+01:00");
+
+    let (status, body) = post(&app, "/api/v1/courses", course).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["fields"]["course_name"].is_array());
+    assert!(body["fields"]["max_seats"].is_array());
+    assert!(body["fields"]["end_date"].is_array());
+}
+
+#[actix_web::test]
+async fn finds_courses_by_id() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, _) = get(&app, &format!("/api/v1/courses/{}", Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = get(&app, "/api/v1/courses/not-a-uuid").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Could not parse course_id as a UUID!");
+}
+
+#[actix_web::test]
+async fn lists_courses_below_a_category() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    // Courses in a subcategory are listed under its parent as well
+    for category_id in [&fixtures.category_id, &fixtures.subcategory_id] {
+        let (status, courses) =
+            get(&app, &format!("/api/v1/categories/{}/courses", category_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(courses[0]["id"], course_id.as_str());
+    }
+
+    let (status, courses) = get(
+        &app,
+        &format!("/api/v1/categories/{}/courses", Uuid::new_v4()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(courses, json!([]));
+}
+
+#[actix_web::test]
+async fn replays_retried_course_creation() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course = course_request(&fixtures, "Arabiska 1");
+
+    let request = |uri: &str| {
+        return TestRequest::post()
+            .uri(uri)
+            .insert_header(("Idempotency-Key", "3f1c5a2e"))
+            .set_json(course.clone());
+    };
+
+    let first = test::call_service(&app, request("/api/v1/courses").to_request()).await;
+    assert_eq!(first.status(), StatusCode::CREATED);
+    let location = first.headers().get(header::LOCATION).unwrap().clone();
+    let first: Value = test::read_body_json(first).await;
+
+    // The old path shares keys with the versioned one
+    for uri in ["/api/v1/courses", "/course"] {
+        let response = test::call_service(&app, request(uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get("Idempotent-Replayed").unwrap(),
+            "true"
+        );
+        assert_eq!(response.headers().get(header::LOCATION).unwrap(), location);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body, first);
+    }
+
+    let (courses,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM db.courses")
+        .fetch_one(&db.db)
+        .await
+        .unwrap();
+    assert_eq!(courses, 1);
+}
@@ -0,0 +1,70 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+use sqlx::Executor;
+
+use super::{get, test_app, TestDb};
+use crate::queries::SCHEMA_VERSION;
+
+#[actix_web::test]
+async fn is_alive() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, body) = get(&app, "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "up", "components": []}));
+}
+
+#[actix_web::test]
+async fn is_ready_with_the_whole_schema() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["components"][0]["name"], "database");
+    assert_eq!(body["components"][0]["status"], "up");
+    assert!(body["components"][0]["latency_ms"].is_number());
+    assert_eq!(body["components"][1]["name"], "schema");
+    assert_eq!(body["components"][1]["status"], "up");
+}
+
+#[actix_web::test]
+async fn is_not_ready_with_another_schema() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    db.db
+        .execute(r#"UPDATE "db"."schema_version" SET "version" = "version" - 1"#)
+        .await
+        .unwrap();
+
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["components"][0]["status"], "up");
+    assert_eq!(body["components"][1]["status"], "down");
+    assert_eq!(
+        body["components"][1]["detail"],
+        format!(
+            "Schema version {}, expected {}",
+            SCHEMA_VERSION - 1,
+            SCHEMA_VERSION
+        )
+    );
+
+    // Built before db_query.sql kept a version
+    db.db
+        .execute(r#"DROP TABLE "db"."schema_version""#)
+        .await
+        .unwrap();
+
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["components"][0]["status"], "up");
+    assert_eq!(
+        body["components"][1]["detail"],
+        format!("No schema version, expected {}", SCHEMA_VERSION)
+    );
+}
@@ -0,0 +1,40 @@
+use actix_web::{
+    http::StatusCode,
+    test::{self, TestRequest},
+};
+
+use super::{add_course, course_request, fixtures, get, png, send, test_app, upload_image, TestDb};
+
+#[actix_web::test]
+async fn makes_uploaded_images_the_course_image() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let request = upload_image(
+        &format!("/api/v1/courses/{}/image", course_id),
+        &png(640, 480),
+        "image/png",
+    );
+    let (status, image) = send(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, course) = get(&app, &format!("/api/v1/courses/{}", course_id)).await;
+    assert_eq!(course["image"], image["original"]);
+
+    // Every size is served from the path the upload was answered with
+    let mut sizes = vec![];
+    for size in ["original", "large", "thumbnail"] {
+        let uri = image[size].as_str().unwrap();
+        assert!(uri.starts_with(&format!("/api/v1/images/courses/{}/", course_id)));
+
+        let response = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = test::read_body(response).await;
+        let served = image::load_from_memory(&bytes).unwrap();
+        sizes.push((served.width(), served.height()));
+    }
+    assert_eq!(sizes, vec![(640, 480), (640, 480), (320, 240)]);
+}
@@ -0,0 +1,25 @@
+use actix_web::{
+    http::StatusCode,
+    test::{self, TestRequest},
+};
+
+use super::{test_app, TestDb};
+
+#[actix_web::test]
+async fn marks_unversioned_paths_as_deprecated() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let response =
+        test::call_service(&app, TestRequest::get().uri("/locations").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("deprecation"));
+
+    let response = test::call_service(
+        &app,
+        TestRequest::get().uri("/api/v1/locations").to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("deprecation"));
+}
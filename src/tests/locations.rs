@@ -0,0 +1,262 @@
+use actix_web::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    add_course, booking_request, course_request, delete, fixtures, get, patch, post, test_app,
+    TestDb,
+};
+use crate::{helpers::slugify, seed::parse_scb_locations};
+
+#[actix_web::test]
+async fn creates_districts_and_cities() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, locations) = get(&app, "/api/v1/locations").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(locations[0]["district_name"], "Stockholms län");
+    assert_eq!(locations[0]["cities"][0]["city_slug"], "stockholm");
+
+    let (status, districts) = get(&app, "/api/v1/districts").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(districts[0]["code"], 1);
+
+    let (status, cities) = get(
+        &app,
+        &format!("/api/v1/districts/{}/cities", fixtures.district_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cities[0]["id"], fixtures.city_id.as_str());
+
+    let (status, city) = get(&app, "/api/v1/locations/by-slug/stockholm").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(city["parent_id"], fixtures.district_id.as_str());
+
+    let (status, city) = get(&app, &format!("/api/v1/locations/{}", fixtures.city_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(city["code"], 180);
+
+    let (status, _) = get(&app, &format!("/api/v1/locations/{}", Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn rejects_duplicate_locations() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/districts",
+        json!({ "name": "Stockholms län", "code": 1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "The district already exists!");
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/cities",
+        json!({ "name": "Stockholm", "district_id": fixtures.district_id, "code": 180 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["message"],
+        "City with this name already exists in this district!"
+    );
+}
+
+#[actix_web::test]
+async fn rejects_cities_outside_their_district() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/cities",
+        json!({ "name": "Solna", "district_id": Uuid::new_v4(), "code": 184 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Parent district does not exist!");
+
+    let (status, body) = post(
+        &app,
+        "/api/v1/cities",
+        json!({ "name": "Uppsala", "district_id": fixtures.district_id, "code": 380 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "City code 380 does not belong to district code 1!"
+    );
+}
+
+#[actix_web::test]
+async fn updates_locations() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let uri = format!("/api/v1/locations/{}", fixtures.city_id);
+
+    let (status, city) = patch(
+        &app,
+        &uri,
+        json!({ "name": "Stockholms stad", "slug": "stockholms-stad" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(city["name"], "Stockholms stad");
+    assert_eq!(city["slug"], "stockholms-stad");
+
+    let (status, _) = patch(&app, &uri, json!({ "code": 380 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = patch(&app, &uri, json!({ "name": "", "slug": "-" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["fields"]["name"].is_array());
+    assert!(body["fields"]["slug"].is_array());
+
+    let (status, body) = patch(
+        &app,
+        &format!("/api/v1/locations/{}", fixtures.district_id),
+        json!({ "district_id": fixtures.district_id }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "Only cities can be moved to another district!"
+    );
+}
+
+#[actix_web::test]
+async fn keeps_codes_in_range_when_recoding() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+
+    let district_uri = format!("/api/v1/locations/{}", fixtures.district_id);
+    let city_uri = format!("/api/v1/locations/{}", fixtures.city_id);
+
+    for (uri, code) in [(&district_uri, 180), (&city_uri, 18), (&city_uri, 10000)] {
+        let (status, body) = patch(&app, uri, json!({ "code": code })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["fields"]["code"].is_array());
+    }
+
+    // Stockholm (0180) would no longer belong to its län
+    let (status, body) = patch(&app, &district_uri, json!({ "code": 3 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["message"],
+        "City code 180 of Stockholm does not belong to district code 3!"
+    );
+
+    let (status, district) = post(
+        &app,
+        "/api/v1/districts",
+        json!({ "name": "Uppsala län", "code": 3 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let uri = format!("/api/v1/locations/{}", district["id"].as_str().unwrap());
+    let (status, district) = patch(&app, &uri, json!({ "code": 4 })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(district["code"], 4);
+}
+
+#[actix_web::test]
+async fn deletes_locations_not_in_use() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let (status, _) = delete(&app, &format!("/api/v1/locations/{}", fixtures.city_id)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, solna) = post(
+        &app,
+        "/api/v1/cities",
+        json!({ "name": "Solna", "district_id": fixtures.district_id, "code": 184 }),
+    )
+    .await;
+    let uri = format!("/api/v1/locations/{}", solna["id"].as_str().unwrap());
+
+    let (status, body) = delete(&app, &uri).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(body.is_null());
+
+    let (status, _) = delete(&app, &uri).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn keeps_the_kommuner_of_booked_participants() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+    let fixtures = fixtures(&app).await;
+    let course_id = add_course(&app, course_request(&fixtures, "Arabiska 1")).await;
+
+    let (_, solna) = post(
+        &app,
+        "/api/v1/cities",
+        json!({ "name": "Solna", "district_id": fixtures.district_id, "code": 184 }),
+    )
+    .await;
+
+    // The course is in Stockholm, only the participant lives in Solna
+    let mut booking = booking_request(&course_id, 199001011239);
+    booking["kommun"] = json!("Solna");
+    let (status, _) = post(&app, "/api/v1/bookings", booking).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let uri = format!("/api/v1/locations/{}", solna["id"].as_str().unwrap());
+    let (status, body) = delete(&app, &uri).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["message"],
+        "The location is the kommun of booked participants!"
+    );
+}
+
+// db_query.sql backfills slugs with db.slugify, which has to agree with the API
+#[actix_web::test]
+async fn backfills_the_slugs_slugify_makes() {
+    let db = TestDb::new().await;
+
+    let mut names: Vec<String> = [
+        "Malmö",
+        "Upplands-Bro",
+        "  Ångermanland – Öst/Väst  ",
+        "Café Señor 2",
+        "åäàá öøó éèêë üú",
+        "ÅÄÀÁ ÖØÓ ÉÈÊË ÜÚ",
+        "--",
+    ]
+    .map(String::from)
+    .to_vec();
+    for lan in parse_scb_locations() {
+        names.push(lan.name);
+        names.extend(lan.kommuner.into_iter().map(|kommun| kommun.name));
+    }
+
+    for name in names {
+        let slug: String = sqlx::query_scalar("SELECT db.slugify($1)")
+            .bind(&name)
+            .fetch_one(&db.db)
+            .await
+            .unwrap();
+        assert_eq!(slug, slugify(&name), "slugs of {:?} differ", name);
+    }
+}
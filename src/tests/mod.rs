@@ -0,0 +1,353 @@
+// Integration tests running every handler in services against a real Postgres,
+// and helpers shared with the in-memory tests next to the handlers.
+// Each test gets a database of its own on the server in TEST_DB_URL, or DB_URL
+// from .env, built from db_query.sql and dropped again when the test is done.
+
+use std::{io::Cursor, str::FromStr, sync::atomic::AtomicBool, time::Duration};
+
+use actix_http::Request;
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceResponse},
+    http::StatusCode,
+    test::{self, TestRequest},
+    web::Data,
+    Error,
+};
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+use serde_json::{json, Value};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, Executor, PgPool,
+};
+use uuid::Uuid;
+
+use crate::{
+    blobstore::MemoryStore, botcheck::NoBotCheck, cache::CatalogueCache, metrics::Metrics,
+    postnummer::DatasetLookup, ratelimit::BookingLimits, repos::postgres::PgRepo,
+    security::HttpPolicy, AppState,
+};
+
+mod attendance;
+mod bookings;
+mod categories;
+mod certificates;
+mod courses;
+mod health;
+mod images;
+mod legacy;
+mod locations;
+mod postnummer;
+
+const SCHEMA: &str = include_str!("../../db_query.sql");
+
+pub struct TestDb {
+    pub state: Data<AppState>,
+    // For looking at rows the API does not return
+    pub db: PgPool,
+    name: String,
+    server: PgConnectOptions,
+}
+
+impl TestDb {
+    pub async fn new() -> TestDb {
+        dotenv::dotenv().ok();
+        let url = std::env::var("TEST_DB_URL")
+            .or_else(|_| std::env::var("DB_URL"))
+            .expect("TEST_DB_URL or DB_URL must point at a Postgres server to run the tests");
+        let server = PgConnectOptions::from_str(&url).expect("Invalid database url");
+
+        let name = format!("ibnrushd_test_{}", Uuid::new_v4().simple());
+
+        let mut connection = server
+            .connect()
+            .await
+            .expect("Could not connect to the test Postgres server");
+        connection
+            .execute(format!("CREATE DATABASE \"{}\"", name).as_str())
+            .await
+            .expect("Could not create the test database");
+
+        let db = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_with(server.clone().database(&name))
+            .await
+            .expect("Could not connect to the test database");
+        db.execute(SCHEMA)
+            .await
+            .expect("Could not apply db_query.sql");
+
+        let repo = PgRepo::new(db.clone());
+
+        let state = Data::new(AppState {
+            courses: Box::new(repo.clone()),
+            bookings: Box::new(repo.clone()),
+            locations: Box::new(repo.clone()),
+            categories: Box::new(repo.clone()),
+            health: Box::new(repo.clone()),
+            idempotency: Box::new(repo),
+            attendance_threshold: 80.0,
+            certificate_issuer: String::from("Ibn Rushd Studieförbund"),
+            idempotency_ttl: Duration::from_secs(24 * 60 * 60),
+            postnummer_lookup: Box::new(DatasetLookup::bundled()),
+            booking_limits: BookingLimits::default(),
+            bot_check: Box::new(NoBotCheck),
+            catalogue: CatalogueCache::new(Duration::from_secs(60)),
+            blobs: Box::new(MemoryStore::default()),
+            metrics: Metrics::new(Some(db.clone())),
+            http: HttpPolicy::default(),
+            shutting_down: AtomicBool::new(false),
+        });
+
+        return TestDb {
+            state,
+            db,
+            name,
+            server,
+        };
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let server = self.server.clone();
+        let drop_database = format!("DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)", self.name);
+
+        // The runtime of the test is being torn down, so use one of our own
+        let dropped = std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let mut connection = server.connect().await?;
+                connection.execute(drop_database.as_str()).await?;
+                return Ok::<(), sqlx::Error>(());
+            })
+        })
+        .join();
+
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Could not drop test database {}", self.name);
+        }
+    }
+}
+
+// The whole app, as served by main, on top of the given state
+macro_rules! test_app {
+    ($state:expr) => {
+        actix_web::test::init_service(crate::app!($state.clone())).await
+    };
+}
+
+pub(crate) use test_app;
+
+// Sends the request and returns the status together with what the JSON
+// envelope holds: `data` when it succeeded, `error` otherwise. Bodies outside
+// the envelope, like the health checks, are returned as they are.
+pub async fn send<S, B>(app: &S, request: TestRequest) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+
+    if body.is_empty() {
+        return (status, Value::Null);
+    }
+
+    let mut json: Value = serde_json::from_slice(&body).expect("Response body is not JSON");
+    for key in ["data", "error"] {
+        if json.get(key).is_some() {
+            return (status, json[key].take());
+        }
+    }
+
+    return (status, json);
+}
+
+pub async fn get<S, B>(app: &S, uri: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    return send(app, TestRequest::get().uri(uri)).await;
+}
+
+pub async fn post<S, B>(app: &S, uri: &str, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    return send(app, TestRequest::post().uri(uri).set_json(body)).await;
+}
+
+pub async fn patch<S, B>(app: &S, uri: &str, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    return send(app, TestRequest::patch().uri(uri).set_json(body)).await;
+}
+
+pub async fn delete<S, B>(app: &S, uri: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    return send(app, TestRequest::delete().uri(uri)).await;
+}
+
+pub fn id(value: &Value) -> String {
+    return value["id"].as_str().expect("No id in response").to_string();
+}
+
+// A district, a city, a category and a subcategory for courses to refer to
+pub struct Fixtures {
+    pub district_id: String,
+    pub city_id: String,
+    pub category_id: String,
+    pub subcategory_id: String,
+}
+
+pub async fn fixtures<S, B>(app: &S) -> Fixtures
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (_, district) = post(
+        app,
+        "/api/v1/districts",
+        json!({ "name": "Stockholms län", "code": 1 }),
+    )
+    .await;
+    let (_, city) = post(
+        app,
+        "/api/v1/cities",
+        json!({ "name": "Stockholm", "district_id": id(&district), "code": 180 }),
+    )
+    .await;
+    let (_, category) = post(
+        app,
+        "/api/v1/categories",
+        json!({ "category_name": "Språk & kultur" }),
+    )
+    .await;
+    let (_, subcategory) = post(
+        app,
+        "/api/v1/subcategories",
+        json!({ "category_name": "Arabiska", "parent_id": id(&category) }),
+    )
+    .await;
+
+    return Fixtures {
+        district_id: id(&district),
+        city_id: id(&city),
+        category_id: id(&category),
+        subcategory_id: id(&subcategory),
+    };
+}
+
+pub fn course_request(fixtures: &Fixtures, name: &str) -> Value {
+    return json!({
+        "course_name": name,
+        "course_description": "Grundkurs",
+        "start_date": "2030-01-07T18:00:00+01:00",
+        "end_date": "2030-03-25T20:00:00+01:00",
+        "csn_entitled": false,
+        "max_seats": 10,
+        "image": "",
+        "days": "Måndagar",
+        "hours": "18-20",
+        "price": 500,
+        "sessions": 4,
+        "visible": true,
+        "city_ids": [fixtures.city_id],
+        "subcategory_ids": [fixtures.subcategory_id],
+    });
+}
+
+// Adds the course and returns its id
+pub async fn add_course<S, B>(app: &S, course: Value) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, body) = post(app, "/api/v1/courses", course).await;
+    assert_eq!(
+        status,
+        StatusCode::CREATED,
+        "Could not add course: {}",
+        body
+    );
+
+    return id(&body);
+}
+
+pub fn booking_request(course_id: &str, personal_number: i64) -> Value {
+    return json!({
+        "personal_number": personal_number,
+        "first_name": "Amina",
+        "last_name": format!("Deltagare {}", personal_number),
+        "address": "Drottninggatan 1",
+        "zipcode": "113 30",
+        "city": "Stockholm",
+        "kommun": "Stockholm",
+        "email": "amina@example.com",
+        "mobile": "070-123 45 67",
+        "course_id": course_id,
+    });
+}
+
+// Books the course and returns the booking id
+pub async fn book<S, B>(app: &S, course_id: &str, personal_number: i64) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let booking = booking_request(course_id, personal_number);
+    let (status, body) = post(app, "/api/v1/bookings", booking.clone()).await;
+    assert_eq!(
+        status,
+        StatusCode::CREATED,
+        "Could not book course: {}",
+        body
+    );
+
+    let (_, attendance) = get(app, &format!("/api/v1/courses/{}/attendance", course_id)).await;
+
+    return attendance["participants"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|participant| participant["last_name"] == booking["last_name"])
+        .map(|participant| participant["booking_id"].as_str().unwrap().to_string())
+        .expect("Booking not listed in attendance");
+}
+
+// A blank PNG of the given size
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = vec![];
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+        .unwrap();
+    return bytes;
+}
+
+// POSTs `bytes` as the "image" field of a multipart/form-data body
+pub fn upload_image(uri: &str, bytes: &[u8], content_type: &str) -> TestRequest {
+    let boundary = "ibnrushd-test-boundary";
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image\"\r\nContent-Type: {}\r\n\r\n",
+        boundary, content_type
+    )
+    .into_bytes();
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    return TestRequest::post()
+        .uri(uri)
+        .insert_header((
+            "Content-Type",
+            format!("multipart/form-data; boundary={}", boundary),
+        ))
+        .set_payload(body);
+}
@@ -0,0 +1,21 @@
+use actix_web::http::StatusCode;
+
+use super::{get, test_app, TestDb};
+
+#[actix_web::test]
+async fn looks_up_postnummer() {
+    let db = TestDb::new().await;
+    let app = test_app!(db.state);
+
+    let (status, area) = get(&app, "/api/v1/postnummer/11330").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(area["postnummer"], "113 30");
+    assert_eq!(area["city"], "Stockholm");
+    assert_eq!(area["kommun_code"], 180);
+
+    let (status, _) = get(&app, "/api/v1/postnummer/1133").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = get(&app, "/api/v1/postnummer/00000").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
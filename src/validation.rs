@@ -0,0 +1,351 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+};
+
+use actix_multipart::MultipartError;
+use actix_web::{
+    dev::Payload,
+    error::{InternalError, JsonPayloadError, PathError, PayloadError, QueryPayloadError},
+    web, Error, FromRequest, HttpRequest, HttpResponse,
+};
+use chrono::{DateTime, NaiveDate};
+use serde::de::DeserializeOwned;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::{envelope, helpers::slugify, models::api::CreateCourseRequest};
+
+fn error(code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(Cow::Borrowed(message));
+    return error;
+}
+
+// Range bounds are stored as floats, show whole numbers without the ".0"
+fn bound(value: &serde_json::Value) -> String {
+    return match value.as_f64() {
+        Some(number) if number.fract() == 0.0 => format!("{}", number as i64),
+        _ => value.to_string(),
+    };
+}
+
+fn message(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+
+    return match error.code.as_ref() {
+        "email" => String::from("Must be a valid email address"),
+        "range" => match (error.params.get("min"), error.params.get("max")) {
+            (Some(min), Some(max)) => format!("Must be between {} and {}", bound(min), bound(max)),
+            (Some(min), None) => format!("Must be at least {}", bound(min)),
+            (None, Some(max)) => format!("Must be at most {}", bound(max)),
+            (None, None) => String::from("Out of range"),
+        },
+        code => format!("Invalid value ({})", code),
+    };
+}
+
+// Collects every failed rule into one 422 response, keyed by field name.
+// Rules spanning several fields name the field they report on in a "field" param.
+pub fn validation_error_response(errors: ValidationErrors) -> HttpResponse {
+    let mut fields: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (field, kind) in errors.into_errors() {
+        if let ValidationErrorsKind::Field(errors) = kind {
+            for error in errors {
+                let field = match error.params.get("field").and_then(|field| field.as_str()) {
+                    Some(field) => field.to_string(),
+                    None => field.to_string(),
+                };
+
+                fields.entry(field).or_default().push(message(&error));
+            }
+        }
+    }
+
+    return invalid_fields(fields);
+}
+
+fn invalid_fields(fields: BTreeMap<String, Vec<String>>) -> HttpResponse {
+    let mut body = envelope::error("Invalid fields!");
+    body.error.fields = Some(fields);
+
+    return HttpResponse::UnprocessableEntity().json(body);
+}
+
+// A JSON request body, like web::Json, except that a field that can't be
+// deserialized, e.g. a malformed postnummer or id, is reported in the same 422
+// as fields failing validation. Bodies that aren't JSON objects at all still
+// get a 400.
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return &self.0;
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return &mut self.0;
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Json<T> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        // Size limit, Content-Type and syntax are checked as for any JSON body
+        let value = web::Json::<serde_json::Value>::from_request(req, payload);
+
+        return Box::pin(async move {
+            let value = value.await?.into_inner();
+            return serde_path_to_error::deserialize(value)
+                .map(Json)
+                .map_err(field_error);
+        });
+    }
+}
+
+fn field_error(err: serde_path_to_error::Error<serde_json::Error>) -> Error {
+    let path = err.path().to_string();
+    let message = err.inner().to_string();
+
+    // Missing fields are reported on the object they are missing from
+    let field = match message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'))
+    {
+        Some(missing) if path == "." => missing.to_string(),
+        Some(missing) => format!("{}.{}", path, missing),
+        None => path,
+    };
+
+    // Structs can also be sent as arrays, those have no field names to report
+    if field == "." || field.starts_with('[') {
+        let message = format!("Invalid request body: {}", message);
+        return bad_request(err, message);
+    }
+
+    let fields = BTreeMap::from([(field, vec![message])]);
+    return InternalError::from_response(err, invalid_fields(fields)).into();
+}
+
+// Bodies, paths and query strings that can't be deserialized all get a 400
+// with a message saying what was wrong, instead of actix' plain text errors
+fn bad_request<E: std::fmt::Debug + std::fmt::Display + 'static>(err: E, message: String) -> Error {
+    let response = HttpResponse::BadRequest().json(envelope::error(message));
+    return InternalError::from_response(err, response).into();
+}
+
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> Error {
+    let message = match &err {
+        JsonPayloadError::Overflow { limit }
+        | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+            let message = format!("Request body is larger than {} bytes!", limit);
+            let response = HttpResponse::PayloadTooLarge().json(envelope::error(message));
+            return InternalError::from_response(err, response).into();
+        }
+        JsonPayloadError::Deserialize(err) => format!("Invalid request body: {}", err),
+        JsonPayloadError::ContentType => String::from("Content-Type must be application/json!"),
+        err => format!("Invalid request body: {}", err),
+    };
+
+    return bad_request(err, message);
+}
+
+// Like json_error_handler, for multipart/form-data bodies of at most `limit` bytes
+pub fn multipart_error_handler(limit: usize) -> impl Fn(MultipartError, &HttpRequest) -> Error {
+    return move |err, _req| {
+        let message = match &err {
+            MultipartError::Payload(PayloadError::Overflow) => {
+                let message = format!("Request body is larger than {} bytes!", limit);
+                let response = HttpResponse::PayloadTooLarge().json(envelope::error(message));
+                return InternalError::from_response(err, response).into();
+            }
+            MultipartError::ContentTypeMissing | MultipartError::ContentTypeIncompatible => {
+                String::from("Content-Type must be multipart/form-data!")
+            }
+            err => format!("Invalid request body: {}", err),
+        };
+
+        return bad_request(err, message);
+    };
+}
+
+pub fn path_error_handler(err: PathError, _req: &HttpRequest) -> Error {
+    let message = match &err {
+        PathError::Deserialize(err) => err.to_string(),
+        err => err.to_string(),
+    };
+
+    return bad_request(err, message);
+}
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> Error {
+    let message = match &err {
+        QueryPayloadError::Deserialize(err) => format!("Invalid query string: {}", err),
+        err => format!("Invalid query string: {}", err),
+    };
+
+    return bad_request(err, message);
+}
+
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(error("blank", "Cannot be empty"));
+    }
+
+    return Ok(());
+}
+
+// The date of birth must exist and the last digit is a Luhn checksum of the
+// ten digits after the century, as in personnummer_validering.js
+pub fn personal_number(value: i64) -> Result<(), ValidationError> {
+    let digits: Vec<u32> = value
+        .to_string()
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect();
+
+    if value < 0 || digits.len() != 12 {
+        return Err(error(
+            "personal_number",
+            "Must be a personnummer with 12 digits, e.g. 199001011239",
+        ));
+    }
+
+    let date = NaiveDate::parse_from_str(&value.to_string()[..8], "%Y%m%d");
+    if date.is_err() {
+        return Err(error(
+            "personal_number",
+            "Must start with a date of birth, e.g. 19900101",
+        ));
+    }
+
+    let checksum: u32 = digits[2..]
+        .iter()
+        .enumerate()
+        .map(|(i, digit)| match i % 2 {
+            0 => digit * 2 / 10 + digit * 2 % 10,
+            _ => *digit,
+        })
+        .sum();
+
+    if !checksum.is_multiple_of(10) {
+        return Err(error(
+            "personal_number",
+            "Is not a valid personnummer, check the last digit",
+        ));
+    }
+
+    return Ok(());
+}
+
+pub fn slug(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() || slugify(value) != value {
+        return Err(error(
+            "slug",
+            "May only contain lowercase letters a-z, digits and single dashes",
+        ));
+    }
+
+    return Ok(());
+}
+
+// Like #[validate(range)], for fields whose range depends on what is stored
+pub fn in_range(
+    field: &'static str,
+    value: i32,
+    min: i32,
+    max: i32,
+) -> Result<(), ValidationErrors> {
+    if (min..=max).contains(&value) {
+        return Ok(());
+    }
+
+    let mut error = ValidationError::new("range");
+    error.add_param(Cow::Borrowed("min"), &min);
+    error.add_param(Cow::Borrowed("max"), &max);
+
+    let mut errors = ValidationErrors::new();
+    errors.add(field, error);
+    return Err(errors);
+}
+
+pub fn rfc3339_date(value: &str) -> Result<(), ValidationError> {
+    if DateTime::parse_from_rfc3339(value).is_err() {
+        return Err(error(
+            "date",
+            "Must be an RFC 3339 date, e.g. 2023-01-31T18:00:00+01:00",
+        ));
+    }
+
+    return Ok(());
+}
+
+pub fn course_dates(course: &CreateCourseRequest) -> Result<(), ValidationError> {
+    let dates = (
+        DateTime::parse_from_rfc3339(&course.start_date),
+        DateTime::parse_from_rfc3339(&course.end_date),
+    );
+
+    // Unparseable dates are already reported on their own fields
+    if let (Ok(start_date), Ok(end_date)) = dates {
+        if end_date < start_date {
+            let mut error = error("date_order", "Cannot be before start_date");
+            error.add_param(Cow::Borrowed("field"), &"end_date");
+            return Err(error);
+        }
+    }
+
+    return Ok(());
+}
+
+// Turns any common way of writing a Swedish mobile number, e.g. "070-123 45 67"
+// or "+46 70 123 45 67", into E.164 form: "+46701234567"
+pub fn normalize_mobile(value: &str) -> Option<String> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')'))
+        .collect();
+
+    let national = if let Some(rest) = digits.strip_prefix("+46") {
+        rest
+    } else if let Some(rest) = digits.strip_prefix("0046") {
+        rest
+    } else {
+        digits.strip_prefix('0')?
+    };
+
+    // Swedish mobile numbers are 07X followed by seven digits, X being 0, 2, 3, 6 or 9
+    let is_mobile = national.len() == 9
+        && national.chars().all(|c| c.is_ascii_digit())
+        && national.starts_with('7')
+        && matches!(national.as_bytes()[1], b'0' | b'2' | b'3' | b'6' | b'9');
+
+    if !is_mobile {
+        return None;
+    }
+
+    return Some(format!("+46{}", national));
+}
+
+pub fn swedish_mobile(value: &str) -> Result<(), ValidationError> {
+    if normalize_mobile(value).is_none() {
+        return Err(error(
+            "mobile",
+            "Must be a Swedish mobile number, e.g. 070-123 45 67",
+        ));
+    }
+
+    return Ok(());
+}